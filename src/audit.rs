@@ -0,0 +1,215 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::Result;
+
+/// The backend of the records of undone deletions.
+pub const UNDO_BACKEND: &str = "undo";
+
+/// A single clean operation, as recorded in the audit log.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    pub repository: PathBuf,
+    pub path: PathBuf,
+    pub bytes: u64,
+    /// How the path was cleaned, e.g. `remove` or `staging`, or `undo` if it was restored
+    pub backend: String,
+    pub error: Option<String>,
+}
+
+impl Record {
+    pub fn new(repository: PathBuf, path: PathBuf, bytes: u64, backend: &str, error: Option<String>) -> Self {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        Self {
+            timestamp,
+            repository,
+            path,
+            bytes,
+            backend: backend.to_string(),
+            error,
+        }
+    }
+
+    /// Formats the record as a line of tab separated values.
+    fn to_line(&self) -> String {
+        format!("{}\t{}\t{}\t{}\t{}\t{}\n",
+                self.timestamp,
+                escape(&self.repository.to_string_lossy()),
+                escape(&self.path.to_string_lossy()),
+                self.bytes,
+                escape(&self.backend),
+                match self.error {
+                    Some(ref e) => format!("error: {}", escape(e)),
+                    None => "ok".to_string(),
+                })
+    }
+
+    fn from_line(line: &str) -> Option<Self> {
+        let mut fields = line.split('\t');
+        let timestamp = fields.next()?.parse().ok()?;
+        let repository = PathBuf::from(unescape(fields.next()?));
+        let path = PathBuf::from(unescape(fields.next()?));
+        let bytes = fields.next()?.parse().ok()?;
+        let backend = unescape(fields.next()?);
+        let result = fields.next()?;
+        let error = if result == "ok" {
+            None
+        } else {
+            Some(unescape(result.trim_start_matches("error: ")))
+        };
+        Some(Self { timestamp, repository, path, bytes, backend, error })
+    }
+}
+
+/// An append-only log of clean operations.
+#[derive(Clone)]
+pub struct AuditLog {
+    path: PathBuf,
+}
+
+impl AuditLog {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// The log under `$XDG_STATE_HOME/rpcl`, falling back to `~/.local/state/rpcl`.
+    pub fn default_location() -> Option<Self> {
+        let state_home = match std::env::var_os("XDG_STATE_HOME") {
+            Some(dir) if PathBuf::from(&dir).is_absolute() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".local").join("state"),
+        };
+        Some(Self::new(state_home.join("rpcl").join("history.log")))
+    }
+
+    pub fn append(&self, record: &Record) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        file.write_all(record.to_line().as_bytes())?;
+        Ok(())
+    }
+
+    /// Reads all records, skipping lines that can't be parsed.
+    pub fn records(&self) -> Result<Vec<Record>> {
+        let content = match std::fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(content.lines().filter_map(Record::from_line).collect())
+    }
+}
+
+/// What a list of records adds up to.
+#[derive(Debug, Default, PartialEq)]
+pub struct Summary {
+    pub deletions: usize,
+    pub failures: usize,
+    pub undone: usize,
+    /// Bytes freed by the deletions that succeeded, less those restored by undoing
+    pub reclaimed: u64,
+}
+
+pub fn summarize(records: &[Record]) -> Summary {
+    let mut summary = Summary::default();
+    let mut restored = 0;
+    for record in records.iter() {
+        if record.backend == UNDO_BACKEND {
+            summary.undone += 1;
+            restored += record.bytes;
+            continue;
+        }
+        summary.deletions += 1;
+        match record.error {
+            Some(_) => summary.failures += 1,
+            None => summary.reclaimed += record.bytes,
+        }
+    }
+    summary.reclaimed = summary.reclaimed.saturating_sub(restored);
+    summary
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n")
+}
+
+fn unescape(s: &str) -> String {
+    let mut result = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('t') => result.push('\t'),
+                Some('n') => result.push('\n'),
+                Some(c) => result.push(c),
+                None => result.push('\\'),
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+/// Formats a Unix timestamp as `YYYY-MM-DD hh:mm:ss` in UTC.
+pub fn format_timestamp(timestamp: u64) -> String {
+    let days = (timestamp / 86400) as i64;
+    let secs = timestamp % 86400;
+    // Converts days since the epoch to a civil date (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", year, month, day, secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_line() {
+        let record = Record {
+            timestamp: 1_600_000_000,
+            repository: PathBuf::from("/src/a\tb"),
+            path: PathBuf::from("/src/a\tb/target"),
+            bytes: 1024,
+            backend: "remove".to_string(),
+            error: Some("permission denied\nreally".to_string()),
+        };
+        assert_eq!(Record::from_line(record.to_line().trim_end()), Some(record.clone()));
+        let record = Record { error: None, ..record };
+        assert_eq!(Record::from_line(record.to_line().trim_end()), Some(record));
+    }
+
+    #[test]
+    fn test_summarize() {
+        let record = |bytes, backend: &str, error: Option<&str>| {
+            Record::new(PathBuf::from("/src/a"), PathBuf::from("/src/a/target"), bytes, backend, error.map(|e| e.to_string()))
+        };
+        let records = vec![
+            record(100, "remove", None),
+            record(50, "staging", None),
+            record(30, "remove", Some("permission denied")),
+            record(50, UNDO_BACKEND, None),
+        ];
+        assert_eq!(summarize(&records), Summary { deletions: 3, failures: 1, undone: 1, reclaimed: 100 });
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00:00");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29 00:00:00");
+        assert_eq!(format_timestamp(1_600_000_000), "2020-09-13 12:26:40");
+    }
+}
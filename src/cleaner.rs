@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::error::{Error, Result};
+use crate::repository::ArtifactKind;
+
+/// How an ignored path gets cleaned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Strategy {
+    /// Removes the path from the filesystem
+    Remove,
+    /// Runs the clean command of the build tool that owns the path,
+    /// falling back to removal when there is no such tool or it fails.
+    /// `node_modules` is removed only after `npm ci --dry-run` succeeds.
+    Tool,
+}
+
+impl Strategy {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "remove" => Some(Strategy::Remove),
+            "tool" => Some(Strategy::Tool),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Strategy::Remove => "remove",
+            Strategy::Tool => "tool",
+        }
+    }
+
+    /// Cleans the path and returns the name of the backend that did it.
+    /// Build tool wrappers checked into the repository, such as `./gradlew`, only run if `wrappers` is set.
+    pub fn clean(&self, path: &Path, wrappers: bool) -> Result<&'static str> {
+        if *self == Strategy::Tool {
+            if let Some(mut tool) = tool_command(path, wrappers) {
+                let success = tool.command
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .status()
+                    .map(|status| status.success())
+                    .unwrap_or(false);
+                if success {
+                    // Tools may leave the path behind, e.g. an emptied `build` directory
+                    if std::fs::symlink_metadata(path).is_ok() {
+                        remove(path)?;
+                    }
+                    return Ok(tool.backend);
+                }
+                if tool.check_only {
+                    return Err(Error::NotReproducible(path.to_path_buf(), tool.backend));
+                }
+                // A missing tool or a failed run falls back to removal
+            }
+        }
+        remove(path)?;
+        Ok("remove")
+    }
+}
+
+pub fn remove(path: &Path) -> Result<()> {
+    if std::fs::symlink_metadata(path)?.is_dir() {
+        std::fs::remove_dir_all(path)?;
+    } else {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// A build tool command for an ignored path.
+struct Tool {
+    backend: &'static str,
+    command: Command,
+    /// Whether the command only checks that the path can be recreated, so that
+    /// a failure keeps the path instead of falling back to removal
+    check_only: bool,
+}
+
+/// Finds the command of the build tool owning `path` from the files next to it.
+fn tool_command(path: &Path, wrappers: bool) -> Option<Tool> {
+    let name = path.file_name()?.to_str()?;
+    let dir = path.parent()?;
+    let (backend, command) = if name == "target" && dir.join("Cargo.toml").is_file() {
+        // Without these, cargo cleans the target directory of the workspace or `$CARGO_TARGET_DIR` instead
+        let mut command = Command::new("cargo");
+        command.arg("clean")
+            .arg("--manifest-path").arg(dir.join("Cargo.toml"))
+            .arg("--target-dir").arg(path)
+            .current_dir(dir);
+        ("cargo clean", command)
+    } else if name == "node_modules" && dir.join("package-lock.json").is_file() {
+        // npm has no clean command, but a dry run of `npm ci` tells whether the lockfile can reinstall the packages
+        return Some(Tool { backend: "npm ci --dry-run", command: command_in(dir, "npm", &["ci", "--dry-run"]), check_only: true });
+    } else if wrappers && (name == "build" || name == ".gradle") && dir.join("gradlew").is_file() {
+        ("gradle clean", command_in(dir, "./gradlew", &["clean"]))
+    } else if (name == "build" || name == ".gradle") && (dir.join("build.gradle").is_file() || dir.join("build.gradle.kts").is_file()) {
+        ("gradle clean", command_in(dir, "gradle", &["clean"]))
+    } else if (name == "bin" || name == "obj") && has_dotnet_project(dir) {
+        ("dotnet clean", command_in(dir, "dotnet", &["clean"]))
+    } else {
+        return None;
+    };
+    Some(Tool { backend, command, check_only: false })
+}
+
+fn command_in(dir: &Path, program: &str, args: &[&str]) -> Command {
+    let mut command = Command::new(program);
+    command.args(args).current_dir(dir);
+    command
+}
+
+fn has_dotnet_project(dir: &Path) -> bool {
+    std::fs::read_dir(dir)
+        .map(|entries| entries.filter_map(|e| e.ok()).any(|e| {
+            let name = e.file_name().to_string_lossy().to_string();
+            name.ends_with(".csproj") || name.ends_with(".fsproj") || name.ends_with(".sln")
+        }))
+        .unwrap_or(false)
+}
+
+/// The strategy to clean each kind of artifact with.
+#[derive(Clone, Default)]
+pub struct Cleaners {
+    strategies: HashMap<ArtifactKind, Strategy>,
+    /// Whether build tool wrappers from repositories may run
+    wrappers: bool,
+}
+
+impl Cleaners {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lets the tool strategy run build tool wrappers such as `./gradlew`, which come from the repositories themselves.
+    pub fn with_wrappers(self, wrappers: bool) -> Self {
+        Self {
+            wrappers,
+            ..self
+        }
+    }
+
+    pub fn strategy(&self, kind: ArtifactKind) -> Strategy {
+        self.strategies.get(&kind).cloned().unwrap_or(Strategy::Remove)
+    }
+
+    /// Cleans `path` with the strategy for `kind` and returns the name of the backend that did it.
+    pub fn clean(&self, kind: ArtifactKind, path: &Path) -> Result<&'static str> {
+        self.strategy(kind).clean(path, self.wrappers)
+    }
+
+    /// Sets a strategy from a `KIND=STRATEGY` specification such as `rust=tool`.
+    pub fn set(&mut self, spec: &str) -> Result<()> {
+        let mut parts = spec.splitn(2, '=');
+        let kind = parts.next().unwrap_or("");
+        let strategy = parts.next().ok_or_else(|| Error::Config(format!("Expected KIND=STRATEGY but got {}", spec)))?;
+        let kind = ArtifactKind::from_name(kind).ok_or_else(|| Error::Config(format!("Unknown artifact kind {}", kind)))?;
+        let strategy = Strategy::from_name(strategy).ok_or_else(|| Error::Config(format!("Unknown cleaning strategy {}", strategy)))?;
+        self.strategies.insert(kind, strategy);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set() {
+        let mut cleaners = Cleaners::new();
+        assert_eq!(cleaners.strategy(ArtifactKind::Rust), Strategy::Remove);
+        cleaners.set("rust=tool").unwrap();
+        assert_eq!(cleaners.strategy(ArtifactKind::Rust), Strategy::Tool);
+        assert_eq!(cleaners.strategy(ArtifactKind::Node), Strategy::Remove);
+        assert!(matches!(cleaners.set("rust"), Err(Error::Config(_))));
+        assert!(cleaners.set("cobol=tool").is_err());
+        assert!(cleaners.set("rust=burn").is_err());
+    }
+
+    #[test]
+    fn test_tool_command() {
+        let dir = std::env::temp_dir().join(format!("rpcl-cleaner-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("gradlew"), "").unwrap();
+        std::fs::write(dir.join("build.gradle"), "").unwrap();
+        std::fs::write(dir.join("package-lock.json"), "{}").unwrap();
+        let program = |tool: Tool| tool.command.get_program().to_owned();
+        assert_eq!(program(tool_command(&dir.join("build"), false).unwrap()), "gradle");
+        assert_eq!(program(tool_command(&dir.join("build"), true).unwrap()), "./gradlew");
+        assert!(tool_command(&dir.join("node_modules"), false).unwrap().check_only);
+        assert!(tool_command(&dir.join("target"), false).is_none());
+        std::fs::write(dir.join("Cargo.toml"), "").unwrap();
+        let cargo = tool_command(&dir.join("target"), false).unwrap();
+        let args = cargo.command.get_args().collect::<Vec<_>>();
+        assert_eq!(args, vec![std::ffi::OsStr::new("clean"), "--manifest-path".as_ref(), dir.join("Cargo.toml").as_os_str(),
+                              "--target-dir".as_ref(), dir.join("target").as_os_str()]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_clean_falls_back_when_the_tool_fails() {
+        let dir = std::env::temp_dir().join(format!("rpcl-cleaner-fallback-test-{}", std::process::id()));
+        let target = dir.join("target");
+        std::fs::create_dir_all(target.join("debug")).unwrap();
+        std::fs::write(dir.join("Cargo.toml"), "not a manifest").unwrap();
+        // `cargo clean` fails on the broken manifest, if cargo runs at all
+        assert_eq!(Strategy::Tool.clean(&target, false).unwrap(), "remove");
+        assert!(!target.exists());
+
+        // A failed check keeps node_modules instead of removing it
+        let node_modules = dir.join("node_modules");
+        std::fs::create_dir_all(&node_modules).unwrap();
+        std::fs::write(dir.join("package-lock.json"), "not a lockfile").unwrap();
+        assert!(matches!(Strategy::Tool.clean(&node_modules, false), Err(Error::NotReproducible(..))));
+        assert!(node_modules.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_clean_removes_what_the_tool_left() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("rpcl-cleaner-left-test-{}", std::process::id()));
+        let build = dir.join("build");
+        std::fs::create_dir_all(build.join("classes")).unwrap();
+        // A wrapper that succeeds without removing anything
+        std::fs::write(dir.join("gradlew"), "#!/bin/sh\nexit 0\n").unwrap();
+        std::fs::set_permissions(dir.join("gradlew"), std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(Strategy::Tool.clean(&build, true).unwrap(), "gradle clean");
+        assert!(!build.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,12 +1,130 @@
-use std::error::Error;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
 use walkdir::{DirEntry, WalkDir};
+use crate::error::Result;
 use crate::repository::*;
-use ignore;
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
 
 pub enum Event {
+    /// A repository was added.
     Update,
-    Done,
+    Progress(Progress),
+    /// A directory outside of repositories couldn't be read, so nothing under it was scanned.
+    Skipped(Warning),
+    Done(Progress),
+}
+
+/// How far the scan under a root directory has got.
+#[derive(Clone, Default)]
+pub struct Progress {
+    pub root: String,
+    /// The directory currently being walked
+    pub current: PathBuf,
+    pub directories: usize,
+    pub repositories: usize,
+    /// Total size of ignored paths measured so far
+    pub bytes: u64,
+    /// Directories outside of repositories that couldn't be read
+    pub skipped: usize,
+}
+
+/// Keeps track of the progress and sends it to the UI at most every `PROGRESS_INTERVAL`.
+struct Reporter {
+    tx: Sender<Event>,
+    progress: Progress,
+    last_sent: Instant,
+}
+
+impl Reporter {
+    fn new(tx: Sender<Event>, root: String) -> Self {
+        Self {
+            tx,
+            progress: Progress {
+                root,
+                ..Progress::default()
+            },
+            last_sent: Instant::now(),
+        }
+    }
+
+    fn visit_directory(&mut self, path: &Path) -> Result<()> {
+        self.progress.directories += 1;
+        if self.last_sent.elapsed() >= PROGRESS_INTERVAL {
+            self.progress.current = path.to_path_buf();
+            self.tx.send(Event::Progress(self.progress.clone()))?;
+            self.last_sent = Instant::now();
+        }
+        Ok(())
+    }
+
+    fn add_bytes(&mut self, bytes: u64) {
+        self.progress.bytes += bytes;
+    }
+
+    fn skip(&mut self, warning: Warning) -> Result<()> {
+        self.progress.skipped += 1;
+        self.tx.send(Event::Skipped(warning))?;
+        Ok(())
+    }
+
+    fn add_repository(&mut self) -> Result<()> {
+        self.progress.repositories += 1;
+        self.tx.send(Event::Update)?;
+        Ok(())
+    }
+
+    fn done(self) -> Result<()> {
+        self.tx.send(Event::Done(self.progress))?;
+        Ok(())
+    }
+}
+
+/// Names of artifacts that build tools recreate, along with the files that
+/// must sit next to them for us to be confident which tool that is.
+const REGENERABLE_ARTIFACTS: &[(&str, &[&str])] = &[
+    ("target", &["Cargo.toml"]),
+    ("node_modules", &["package.json", "package-lock.json", "yarn.lock", "pnpm-lock.yaml"]),
+    (".venv", &["pyproject.toml", "requirements.txt", "setup.py", "Pipfile"]),
+    ("venv", &["pyproject.toml", "requirements.txt", "setup.py", "Pipfile"]),
+    ("__pycache__", &[]),
+    (".gradle", &["build.gradle", "build.gradle.kts", "settings.gradle", "settings.gradle.kts"]),
+    ("build", &["build.gradle", "build.gradle.kts"]),
+];
+
+/// Tells whether a build tool can recreate the path, judging from its name and siblings.
+fn is_regenerable(path: &Path) -> bool {
+    let name = match path.file_name().and_then(|n| n.to_str()) {
+        Some(name) => name,
+        None => return false,
+    };
+    let parent = match path.parent() {
+        Some(parent) => parent,
+        None => return false,
+    };
+    REGENERABLE_ARTIFACTS.iter()
+        .filter(|(artifact, _)| *artifact == name)
+        .any(|(_, markers)| markers.is_empty() || markers.iter().any(|m| parent.join(m).is_file()))
+}
+
+/// Root label of the global caches, which aren't found under any root directory.
+pub const GLOBAL_CACHES_ROOT: &str = "global caches";
+
+/// Package caches kept by build tools in the home directory, relative to it.
+const GLOBAL_CACHES: &[&str] = &[
+    ".npm/_cacache",
+    ".gradle/caches",
+    ".m2/repository",
+];
+
+/// Lists the locations of the global caches, honoring `$CARGO_HOME` and `$XDG_CACHE_HOME`.
+fn global_cache_paths(home: &Path, cargo_home: Option<PathBuf>, cache_home: Option<PathBuf>) -> Vec<PathBuf> {
+    let cargo_home = cargo_home.filter(|p| p.is_absolute()).unwrap_or_else(|| home.join(".cargo"));
+    let cache_home = cache_home.filter(|p| p.is_absolute()).unwrap_or_else(|| home.join(".cache"));
+    let mut paths = vec![cargo_home.join("registry"), cache_home.join("pip")];
+    paths.extend(GLOBAL_CACHES.iter().map(|cache| home.join(cache)));
+    paths
 }
 
 fn is_repository_mark_directory(entry: &DirEntry) -> bool {
@@ -14,16 +132,27 @@ fn is_repository_mark_directory(entry: &DirEntry) -> bool {
 }
 
 fn is_hidden_directory(entry: &DirEntry) -> bool {
-    entry.file_type().is_dir() && entry.file_name().to_str().map(|s| s.starts_with(".")).unwrap_or(false)
+    // The root itself is walked even when given as `.`
+    entry.depth() > 0 && entry.file_type().is_dir() && entry.file_name().to_str().map(|s| s.starts_with(".")).unwrap_or(false)
 }
 
-fn calculate_size(root_entry: DirEntry) -> Result<u64, Box<dyn Error>> {
-    let mut it = WalkDir::new(root_entry.path()).follow_links(false).into_iter();
+/// Turns an error of walking `root` into a warning about the entry it happened at.
+fn walk_warning(e: walkdir::Error, root: &Path) -> Warning {
+    let path = e.path().unwrap_or(root).to_path_buf();
+    Warning::new(path, &e.into())
+}
+
+/// Sums up the sizes of the entries under `root_path`. Entries that can't be read are skipped with a warning.
+fn calculate_size(root_path: &Path, reporter: &mut Reporter, warnings: &mut Vec<Warning>) -> Result<u64> {
+    let mut it = WalkDir::new(root_path).follow_links(false).into_iter();
     let mut size = 0u64;
     loop {
         let entry = match it.next() {
             None => break,
-            Some(Err(_)) => continue,
+            Some(Err(e)) => {
+                warnings.push(walk_warning(e, root_path));
+                continue;
+            },
             Some(Ok(entry)) => entry,
         };
         if entry.path_is_symlink() {
@@ -32,59 +161,194 @@ fn calculate_size(root_entry: DirEntry) -> Result<u64, Box<dyn Error>> {
         if entry.file_name().to_str().map(|s| s == ".git").unwrap_or(false) {
             continue;
         }
-        size += entry.metadata()?.len();
+        if entry.file_type().is_dir() {
+            reporter.visit_directory(entry.path())?;
+        }
+        let len = match entry.metadata() {
+            Ok(metadata) => metadata.len(),
+            Err(e) => {
+                warnings.push(walk_warning(e, root_path));
+                continue;
+            },
+        };
+        reporter.add_bytes(len);
+        size += len;
     }
     Ok(size)
 }
 
 // TODO: This only looks at the gitignore at the repository root
 /// Collects information of paths listed in `.gitignore`.
-fn collect_ignored_path_infos(repository_path: PathBuf) -> Result<Vec<IgnoredPathInfo>, Box<dyn Error>> {
+/// Entries that can't be read are skipped with a warning rather than failing the whole repository.
+fn collect_ignored_path_infos(repository_path: PathBuf, reporter: &mut Reporter, warnings: &mut Vec<Warning>) -> Result<Vec<IgnoredPathInfo>> {
     let mut ignored_path_infos = Vec::new();
     let mut gitignore_path = repository_path.clone();
     gitignore_path.push(".gitignore");
-    let (ignore, _) = ignore::gitignore::Gitignore::new(gitignore_path);
+    let (ignore, error) = ignore::gitignore::Gitignore::new(&gitignore_path);
+    if let Some(e) = error {
+        // The lines that could be parsed are still used
+        warnings.push(Warning::new(gitignore_path, &e.into()));
+    }
     // I don't want to bother with symlinks within repositories
-    let mut it = WalkDir::new(repository_path).follow_links(false).into_iter();
+    let mut it = WalkDir::new(&repository_path).follow_links(false).into_iter();
     loop {
         let entry = match it.next() {
             None => break,
-            Some(Err(_)) => continue,
+            Some(Err(e)) => {
+                warnings.push(walk_warning(e, &repository_path));
+                continue;
+            },
             Some(Ok(entry)) => entry,
         };
         if ignore.matched(entry.path(), entry.file_type().is_dir()).is_ignore() {
-            let size = calculate_size(entry.clone())?;
-            ignored_path_infos.push(IgnoredPathInfo::new(entry.clone().into_path(), size));
+            let size = calculate_size(entry.path(), reporter, warnings)?;
+            let regenerable = is_regenerable(entry.path());
+            let ignored_path_info = IgnoredPathInfo::new(entry.clone().into_path(), size, regenerable);
+            let sweepable = regenerable && ignored_path_info.kind() == ArtifactKind::Rust;
+            ignored_path_infos.push(ignored_path_info.with_sweepable(sweepable));
             it.skip_current_dir();
+        } else if entry.file_type().is_dir() {
+            reporter.visit_directory(entry.path())?;
         }
     }
     Ok(ignored_path_infos)
 }
 
 /// Collects all paths that are considered a git repository.
-pub fn collect_repositories(root_path: String, repositories: RepositoryStore, tx: std::sync::mpsc::Sender<Event>) -> Result<(), Box<dyn Error>> {
-    let mut it = WalkDir::new(root_path).follow_links(true).into_iter();
+pub fn collect_repositories(root_path: String, repositories: RepositoryStore, tx: Sender<Event>) -> Result<()> {
+    let mut reporter = Reporter::new(tx, root_path.clone());
+    let mut it = WalkDir::new(&root_path).follow_links(true).into_iter();
     loop {
         let entry = match it.next() {
             None => break,
-            Some(Err(_)) => continue,
+            Some(Err(e)) => {
+                reporter.skip(walk_warning(e, Path::new(&root_path)))?;
+                continue;
+            },
             Some(Ok(entry)) => entry,
         };
+        if entry.file_type().is_dir() {
+            reporter.visit_directory(entry.path())?;
+        }
         if is_repository_mark_directory(&entry) {
             let repository_path = {
                 let mut path = entry.clone().into_path();
                 path.pop();
                 path
             };
-            repositories.add(Repository::new(repository_path.clone(), collect_ignored_path_infos(repository_path)?))?;
-            tx.send(Event::Update)?;
+            // The same repository may be reachable through another root or a symlink
+            if !repositories.contains(&repository_path)? {
+                let mut warnings = Vec::new();
+                let ignored_path_infos = collect_ignored_path_infos(repository_path.clone(), &mut reporter, &mut warnings)?;
+                let repository = Repository::new(repository_path, root_path.clone(), ignored_path_infos).with_warnings(warnings);
+                if repositories.add(repository)? {
+                    reporter.add_repository()?;
+                }
+            }
         }
         if is_hidden_directory(&entry) {
             it.skip_current_dir();
         }
     }
-    tx.send(Event::Done)?;
-    Ok(())
+    reporter.done()
 }
 
+/// Collects the global caches that exist into a pseudo repository at the home directory.
+pub fn collect_global_caches(repositories: RepositoryStore, tx: Sender<Event>) -> Result<()> {
+    let mut reporter = Reporter::new(tx, GLOBAL_CACHES_ROOT.to_owned());
+    let home = match std::env::var_os("HOME") {
+        Some(home) => PathBuf::from(home),
+        None => return reporter.done(),
+    };
+    let paths = global_cache_paths(&home, std::env::var_os("CARGO_HOME").map(PathBuf::from), std::env::var_os("XDG_CACHE_HOME").map(PathBuf::from));
+    let mut ignored_path_infos = Vec::new();
+    let mut warnings = Vec::new();
+    for path in paths.into_iter().filter(|p| p.is_dir()) {
+        let size = calculate_size(&path, &mut reporter, &mut warnings)?;
+        // Shared by every project on the machine and slow to download again, so never selected by default
+        ignored_path_infos.push(IgnoredPathInfo::new(path, size, false));
+    }
+    if !ignored_path_infos.is_empty() && repositories.add(Repository::global_caches(home, ignored_path_infos).with_warnings(warnings))? {
+        reporter.add_repository()?;
+    }
+    reporter.done()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_global_cache_paths() {
+        let home = Path::new("/home/user");
+        let paths = global_cache_paths(home, None, None);
+        assert!(paths.contains(&PathBuf::from("/home/user/.cargo/registry")));
+        assert!(paths.contains(&PathBuf::from("/home/user/.cache/pip")));
+        assert!(paths.contains(&PathBuf::from("/home/user/.m2/repository")));
+        let paths = global_cache_paths(home, Some(PathBuf::from("/opt/cargo")), Some(PathBuf::from("relative")));
+        assert!(paths.contains(&PathBuf::from("/opt/cargo/registry")));
+        assert!(paths.contains(&PathBuf::from("/home/user/.cache/pip")));
+    }
+
+    #[test]
+    fn test_is_hidden_directory() {
+        let root = std::env::temp_dir().join(format!("rpcl-hidden-test-{}", std::process::id()));
+        std::fs::create_dir_all(root.join(".hidden")).unwrap();
+        let entries = WalkDir::new(&root).into_iter().collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        let hidden = entries.iter().filter(|e| is_hidden_directory(e)).map(|e| e.file_name().to_owned()).collect::<Vec<_>>();
+        assert_eq!(hidden, vec![std::ffi::OsString::from(".hidden")]);
+        // A root is walked even when its name starts with a dot, as `.` does
+        let entries = WalkDir::new(".").max_depth(0).into_iter().collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        assert!(!is_hidden_directory(&entries[0]));
+        let entries = WalkDir::new(root.join(".hidden")).max_depth(0).into_iter().collect::<std::result::Result<Vec<_>, _>>().unwrap();
+        assert!(!is_hidden_directory(&entries[0]));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
 
+    #[test]
+    fn test_is_regenerable() {
+        let root = std::env::temp_dir().join(format!("rpcl-regenerable-test-{}", std::process::id()));
+        // (directory holding the path, marker files next to it, path name, expected)
+        let cases: &[(&str, &[&str], &str, bool)] = &[
+            ("rust", &["Cargo.toml"], "target", true),
+            ("maven", &["pom.xml"], "target", false),
+            ("npm", &["package.json"], "node_modules", true),
+            ("yarn", &["yarn.lock"], "node_modules", true),
+            ("bare", &[], "node_modules", false),
+            ("poetry", &["pyproject.toml"], ".venv", true),
+            ("pip", &["requirements.txt"], "venv", true),
+            ("python", &[], "__pycache__", true),
+            ("gradle", &["settings.gradle.kts"], ".gradle", true),
+            ("gradle-build", &["build.gradle"], "build", true),
+            ("make", &["Makefile"], "build", false),
+            ("other", &["Cargo.toml"], "dist", false),
+        ];
+        for (dir, markers, name, expected) in cases {
+            let dir = root.join(dir);
+            std::fs::create_dir_all(dir.join(name)).unwrap();
+            for marker in markers.iter() {
+                std::fs::write(dir.join(marker), "").unwrap();
+            }
+            assert_eq!(is_regenerable(&dir.join(name)), *expected, "{}", dir.join(name).display());
+        }
+        // Markers have to be files, not directories of the same name
+        std::fs::create_dir_all(root.join("fake").join("Cargo.toml")).unwrap();
+        assert!(!is_regenerable(&root.join("fake").join("target")));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_collect_repositories_reports_skipped_directories() {
+        let root = std::env::temp_dir().join(format!("rpcl-skipped-test-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        // Followed links make this a filesystem loop, which walkdir reports as an error
+        std::os::unix::fs::symlink(&root, root.join("loop")).unwrap();
+        let (tx, rx) = std::sync::mpsc::channel();
+        collect_repositories(root.to_string_lossy().to_string(), RepositoryStore::new(), tx).unwrap();
+        let events = rx.try_iter().collect::<Vec<_>>();
+        assert!(events.iter().any(|e| matches!(e, Event::Skipped(w) if w.path == root.join("loop"))));
+        assert!(matches!(events.last(), Some(Event::Done(progress)) if progress.skipped == 1));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,62 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+
+use rpcl::{Error, Result};
+
+/// Settings read from a TOML file, by default `$XDG_CONFIG_HOME/rpcl/config.toml`.
+///
+/// ```toml
+/// [keys]
+/// open = ["l", "Enter"]
+/// back = ["h", "Enter"]
+/// help = ["?"]
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Keys by action name. Keys given for an action replace its default keys.
+    #[serde(default)]
+    pub keys: BTreeMap<String, Vec<String>>,
+}
+
+impl Config {
+    pub fn location() -> Option<PathBuf> {
+        let config_home = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if PathBuf::from(&dir).is_absolute() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(config_home.join("rpcl").join("config.toml"))
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Self::parse(&content)
+    }
+
+    /// Loads the file at `path` if there is one, and the defaults otherwise.
+    pub fn load_if_exists(path: &Path) -> Result<Self> {
+        if path.exists() {
+            Self::load(path)
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    pub fn parse(content: &str) -> Result<Self> {
+        toml::from_str(content).map_err(|e| Error::Config(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let config = Config::parse("[keys]\nopen = [\"l\", \"Enter\"]\n").unwrap();
+        assert_eq!(config.keys["open"], vec!["l".to_owned(), "Enter".to_owned()]);
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+        assert!(Config::parse("[colors]\n").is_err());
+    }
+}
@@ -0,0 +1,207 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, Metadata};
+use std::hash::Hasher;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use rpcl::Result;
+use rpcl::Repository;
+
+/// Files with identical content found in more than one place.
+pub struct DuplicateGroup {
+    /// Size of each copy
+    pub size: u64,
+    pub paths: Vec<PathBuf>,
+}
+
+impl DuplicateGroup {
+    /// Bytes that would be freed by keeping a single copy.
+    pub fn wasted(&self) -> u64 {
+        self.size * (self.paths.len() as u64 - 1)
+    }
+}
+
+/// How much of the content of ignored paths also exists elsewhere.
+pub struct DuplicateReport {
+    /// Sorted by wasted bytes, largest first
+    pub groups: Vec<DuplicateGroup>,
+    /// Bytes of each ignored path that have a copy in another place, largest first
+    pub by_ignored_path: Vec<(PathBuf, u64)>,
+}
+
+impl DuplicateReport {
+    pub fn wasted(&self) -> u64 {
+        self.groups.iter().map(|g| g.wasted()).sum()
+    }
+}
+
+/// A file found inside an ignored path.
+struct Candidate {
+    path: PathBuf,
+    ignored_path: PathBuf,
+}
+
+/// Hashes the files inside the ignored paths of `repositories` and groups the identical ones.
+///
+/// Only files of equal size are hashed, and files with equal hashes are compared
+/// byte by byte. Hard links to the same file, as made by shared stores such as
+/// pnpm's, aren't counted as copies on Unix.
+pub fn analyze(repositories: &[Repository]) -> DuplicateReport {
+    let mut by_size: HashMap<u64, Vec<Candidate>> = HashMap::new();
+    let mut inodes = HashSet::new();
+    for repository in repositories {
+        for info in repository.ignored_path_infos().iter() {
+            for entry in WalkDir::new(info.path()).follow_links(false).into_iter().filter_map(|e| e.ok()) {
+                if !entry.file_type().is_file() {
+                    continue;
+                }
+                let metadata = match entry.metadata() {
+                    Ok(metadata) => metadata,
+                    Err(_) => continue,
+                };
+                if metadata.len() == 0 || file_id(&metadata).map(|id| !inodes.insert(id)).unwrap_or(false) {
+                    continue;
+                }
+                by_size.entry(metadata.len()).or_default().push(Candidate {
+                    path: entry.into_path(),
+                    ignored_path: info.path().to_path_buf(),
+                });
+            }
+        }
+    }
+
+    let mut groups = Vec::new();
+    let mut by_ignored_path = HashMap::new();
+    for (size, candidates) in by_size.into_iter().filter(|(_, c)| c.len() > 1) {
+        let mut by_hash: HashMap<u64, Vec<Candidate>> = HashMap::new();
+        for candidate in candidates {
+            if let Ok(hash) = hash_file(&candidate.path) {
+                by_hash.entry(hash).or_default().push(candidate);
+            }
+        }
+        let classes = by_hash.into_iter()
+            .filter(|(_, c)| c.len() > 1)
+            .flat_map(|(_, candidates)| equal_content(candidates));
+        for copies in classes.filter(|c| c.len() > 1) {
+            for copy in copies.iter() {
+                *by_ignored_path.entry(copy.ignored_path.clone()).or_insert(0) += size;
+            }
+            groups.push(DuplicateGroup {
+                size,
+                paths: copies.into_iter().map(|c| c.path).collect(),
+            });
+        }
+    }
+    groups.sort_by_key(|g| std::cmp::Reverse(g.wasted()));
+    let mut by_ignored_path = by_ignored_path.into_iter().collect::<Vec<_>>();
+    by_ignored_path.sort_by_key(|(_, bytes)| std::cmp::Reverse(*bytes));
+    DuplicateReport { groups, by_ignored_path }
+}
+
+#[cfg(unix)]
+fn file_id(metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// Splits files with the same hash into groups of identical content, so that hash collisions aren't taken for copies.
+fn equal_content(candidates: Vec<Candidate>) -> Vec<Vec<Candidate>> {
+    let mut classes: Vec<Vec<Candidate>> = Vec::new();
+    for candidate in candidates {
+        let class = classes.iter_mut()
+            .find(|class| same_content(&class[0].path, &candidate.path).unwrap_or(false));
+        match class {
+            Some(class) => class.push(candidate),
+            None => classes.push(vec![candidate]),
+        }
+    }
+    classes
+}
+
+fn same_content(a: &Path, b: &Path) -> io::Result<bool> {
+    let (mut a, mut b) = (File::open(a)?, File::open(b)?);
+    let mut buffer_a = [0u8; 64 * 1024];
+    let mut buffer_b = [0u8; 64 * 1024];
+    loop {
+        let len = a.read(&mut buffer_a)?;
+        if len == 0 {
+            return Ok(b.read(&mut buffer_b)? == 0);
+        }
+        b.read_exact(&mut buffer_b[..len])?;
+        if buffer_a[..len] != buffer_b[..len] {
+            return Ok(false);
+        }
+    }
+}
+
+fn hash_file(path: &Path) -> Result<u64> {
+    let mut file = File::open(path)?;
+    let mut hasher = DefaultHasher::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let len = file.read(&mut buffer)?;
+        if len == 0 {
+            break;
+        }
+        hasher.write(&buffer[..len]);
+    }
+    Ok(hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rpcl::IgnoredPathInfo;
+
+    #[test]
+    fn test_analyze() {
+        let root = std::env::temp_dir().join(format!("rpcl-duplicates-test-{}", std::process::id()));
+        let write = |path: PathBuf, content: &str| {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        };
+        let a = root.join("a").join("node_modules");
+        let b = root.join("b").join("node_modules");
+        write(a.join("left-pad").join("index.js"), "module.exports = pad");
+        write(b.join("left-pad").join("index.js"), "module.exports = pad");
+        write(a.join("other.js"), "aaaa");
+        write(b.join("other.js"), "bbbb");
+        #[cfg(unix)]
+        std::fs::hard_link(a.join("other.js"), a.join("linked.js")).unwrap();
+
+        let repositories = vec![
+            Repository::new(root.join("a"), ".".to_owned(), vec![IgnoredPathInfo::new(a.clone(), 0, true)]),
+            Repository::new(root.join("b"), ".".to_owned(), vec![IgnoredPathInfo::new(b.clone(), 0, true)]),
+        ];
+        let report = analyze(&repositories);
+        assert_eq!(report.groups.len(), 1);
+        assert_eq!(report.wasted(), 20);
+        assert_eq!(report.by_ignored_path.len(), 2);
+        assert!(report.by_ignored_path.iter().all(|(_, bytes)| *bytes == 20));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_equal_content() {
+        let root = std::env::temp_dir().join(format!("rpcl-equal-content-test-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let candidate = |name: &str, content: &str| {
+            std::fs::write(root.join(name), content).unwrap();
+            Candidate { path: root.join(name), ignored_path: root.clone() }
+        };
+        // As if the hashes of all three had collided
+        let classes = equal_content(vec![candidate("a", "same"), candidate("b", "diff"), candidate("c", "same")]);
+        let names = classes.iter()
+            .map(|class| class.iter().map(|c| c.path.file_name().unwrap().to_string_lossy().to_string()).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec![vec!["a", "c"], vec!["b"]]);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
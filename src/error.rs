@@ -0,0 +1,176 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::sync::PoisonError;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Errors of scanning, cleaning and the TUI.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing the filesystem failed
+    Io(io::Error),
+    /// A path couldn't be read or changed for lack of permissions
+    PermissionDenied(io::Error),
+    /// A thread panicked while holding a lock on shared state
+    LockPoisoned,
+    /// A `.gitignore` couldn't be parsed
+    Ignore(ignore::Error),
+    /// A repository or ignored path is gone, e.g. because it was cleaned in the meantime
+    NotFound(PathBuf),
+    /// A scan or the UI stopped while the other end was still sending
+    Disconnected,
+    /// Drawing to or reading from the terminal failed
+    Terminal(io::Error),
+    /// A saved scan couldn't be read or written
+    Json(serde_json::Error),
+    /// The config file couldn't be parsed or has invalid settings
+    Config(String),
+    /// A background thread panicked while the TUI was running, with the panic message
+    Panicked(String),
+    /// A path was kept because the command checking that it can be recreated failed, with that command
+    NotReproducible(PathBuf, &'static str),
+}
+
+impl Error {
+    /// A short description of the kind of error, used to summarize warnings.
+    pub fn category(&self) -> &'static str {
+        match self {
+            Error::Io(_) => "IO error",
+            Error::PermissionDenied(_) => "permission denied",
+            Error::LockPoisoned => "lock poisoned",
+            Error::Ignore(_) => "invalid .gitignore",
+            Error::NotFound(_) => "not found",
+            Error::Disconnected => "disconnected",
+            Error::Terminal(_) => "terminal error",
+            Error::Json(_) => "invalid JSON",
+            Error::Config(_) => "invalid config",
+            Error::Panicked(_) => "panic",
+            Error::NotReproducible(..) => "not reproducible",
+        }
+    }
+
+    /// Whether the TUI can show the error and keep running.
+    pub fn is_recoverable(&self) -> bool {
+        match self {
+            Error::Io(_) | Error::PermissionDenied(_) | Error::Ignore(_) | Error::NotFound(_) | Error::Json(_) | Error::Config(_) | Error::NotReproducible(..) => true,
+            Error::LockPoisoned | Error::Disconnected | Error::Terminal(_) | Error::Panicked(_) => false,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::PermissionDenied(e) => write!(f, "{}", e),
+            Error::LockPoisoned => write!(f, "Shared state was left inconsistent by a crashed thread"),
+            Error::Ignore(e) => write!(f, "Invalid .gitignore: {}", e),
+            Error::NotFound(path) => write!(f, "{} not found", path.to_string_lossy()),
+            Error::Disconnected => write!(f, "A background thread stopped unexpectedly"),
+            Error::Terminal(e) => write!(f, "Terminal error: {}", e),
+            Error::Json(e) => write!(f, "{}", e),
+            Error::Config(message) => write!(f, "Invalid config: {}", message),
+            Error::Panicked(message) => write!(f, "A background thread {}", message),
+            Error::NotReproducible(path, check) => write!(f, "`{}` failed, so {} may not be reproducible", check, path.to_string_lossy()),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) | Error::PermissionDenied(e) | Error::Terminal(e) => Some(e),
+            Error::Ignore(e) => Some(e),
+            Error::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::PermissionDenied => Error::PermissionDenied(e),
+            _ => Error::Io(e),
+        }
+    }
+}
+
+impl From<walkdir::Error> for Error {
+    fn from(e: walkdir::Error) -> Self {
+        let path = e.path().map(|p| p.to_path_buf());
+        match e.into_io_error() {
+            Some(e) => e.into(),
+            None => Error::Io(io::Error::other(format!("Filesystem loop at {}", path.unwrap_or_default().to_string_lossy()))),
+        }
+    }
+}
+
+impl From<ignore::Error> for Error {
+    fn from(e: ignore::Error) -> Self {
+        Error::Ignore(e)
+    }
+}
+
+impl<T> From<PoisonError<T>> for Error {
+    fn from(_: PoisonError<T>) -> Self {
+        Error::LockPoisoned
+    }
+}
+
+impl<T> From<std::sync::mpsc::SendError<T>> for Error {
+    fn from(_: std::sync::mpsc::SendError<T>) -> Self {
+        Error::Disconnected
+    }
+}
+
+impl From<std::sync::mpsc::RecvError> for Error {
+    fn from(_: std::sync::mpsc::RecvError) -> Self {
+        Error::Disconnected
+    }
+}
+
+#[cfg(feature = "cli")]
+impl From<crossterm::ErrorKind> for Error {
+    fn from(e: crossterm::ErrorKind) -> Self {
+        match e {
+            // crossterm doesn't include the cause in its own message
+            crossterm::ErrorKind::IoError(e) => Error::Terminal(e),
+            e => Error::Terminal(io::Error::other(e.to_string())),
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_io_error() {
+        let e: Error = io::Error::from(io::ErrorKind::PermissionDenied).into();
+        assert!(matches!(e, Error::PermissionDenied(_)));
+        assert!(e.is_recoverable());
+        let e: Error = io::Error::from(io::ErrorKind::NotFound).into();
+        assert!(matches!(e, Error::Io(_)));
+    }
+
+    #[test]
+    fn test_from_poison_error() {
+        let lock = std::sync::Arc::new(std::sync::RwLock::new(()));
+        let poisoner = lock.clone();
+        let _ = std::thread::spawn(move || {
+            let _guard = poisoner.write().unwrap();
+            panic!("poisoning the lock");
+        }).join();
+        let e: Error = lock.read().unwrap_err().into();
+        assert!(matches!(e, Error::LockPoisoned));
+        assert!(!e.is_recoverable());
+    }
+}
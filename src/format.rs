@@ -0,0 +1,21 @@
+use number_prefix::{NumberPrefix, Standalone, Prefixed};
+
+/// Formats a size in bytes with a binary prefix, right-aligned so that sizes line up in columns.
+pub fn size_str(size: u64) -> String {
+    match NumberPrefix::binary(size as f64) {
+        Standalone(bytes) => format!("{:>6}   B", bytes),
+        Prefixed(prefix, n) => format!("{:>6.1} {}B", n, prefix),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_size_str() {
+        assert_eq!(size_str(0), "     0   B");
+        assert_eq!(size_str(1536), "   1.5 KiB");
+        assert_eq!(size_str(5 << 30), "   5.0 GiB");
+    }
+}
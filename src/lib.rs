@@ -0,0 +1,34 @@
+//! Locates git repositories and the resources they ignore, and cleans them up.
+//!
+//! ```no_run
+//! use rpcl::Scanner;
+//!
+//! let repositories = Scanner::new().with_roots(vec!["."]).scan().unwrap();
+//! for repository in repositories.repositories_sorted().unwrap() {
+//!     println!("{} {}", repository.size(), repository.path().display());
+//! }
+//! ```
+
+mod error;
+pub use error::{Error, Result};
+
+mod repository;
+pub use repository::{ArtifactKind, IgnoredPathInfo, Repository, RepositoryStore, Warning};
+
+mod scanner;
+pub use scanner::Scanner;
+
+mod sweep;
+pub use sweep::SweepReport;
+
+mod collector;
+pub use collector::{Event, Progress};
+
+/// Logging of cleaned paths, for `RepositoryStore::with_audit_log`.
+pub mod audit;
+
+/// Ways of cleaning paths, for `RepositoryStore::with_cleaners`.
+pub mod cleaner;
+
+/// Undoable deletions, for `RepositoryStore::with_staging`.
+pub mod staging;
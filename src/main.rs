@@ -1,14 +1,24 @@
 use std::error::Error;
-use std::thread;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
-use std::sync::mpsc::channel;
-use clap;
 
-mod repository;
-use repository::{RepositoryStore};
+use rpcl::{RepositoryStore, Scanner};
+use rpcl::audit::{self, AuditLog, format_timestamp};
+use rpcl::cleaner::Cleaners;
+use rpcl::staging::Staging;
 
-mod collector;
-use collector::*;
+mod config;
+use config::Config;
+
+mod duplicates;
+
+mod format;
+use format::size_str;
+
+mod report;
+
+mod snapshot;
+use snapshot::Snapshot;
 
 mod tui;
 use tui::*;
@@ -19,24 +29,407 @@ fn main() -> Result<(), Box<dyn Error>> {
         .author("tdgne")
         .about("Locates repositories")
         .arg(clap::Arg::with_name("DIR")
-             .help("Sets the root directory to start searching")
+             .help("Sets the root directories to start searching. A directory named like a subcommand has to be given as a path, e.g. ./history")
+             .multiple(true)
              .index(1))
+        .arg(clap::Arg::with_name("theme")
+             .long("theme")
+             .takes_value(true)
+             .possible_values(THEME_NAMES)
+             .help("Sets the color theme (defaults to monochrome if NO_COLOR is set, dark otherwise)"))
+        .arg(clap::Arg::with_name("long-paths")
+             .long("long-paths")
+             .takes_value(true)
+             .possible_values(OVERFLOW_NAMES)
+             .default_value("scroll")
+             .help("Sets how paths too long for the terminal are shown: scrolling through the selected one, or with their middles elided"))
+        .arg(clap::Arg::with_name("path-display")
+             .long("path-display")
+             .takes_value(true)
+             .possible_values(PATH_DISPLAY_NAMES)
+             .default_value("full")
+             .help("Sets how repository paths are shown: as found, with ~ for the home directory, relative to their root, or by name followed by the parent"))
+        .arg(clap::Arg::with_name("config")
+             .long("config")
+             .takes_value(true)
+             .value_name("FILE")
+             .help("Reads settings such as key bindings from FILE instead of $XDG_CONFIG_HOME/rpcl/config.toml"))
+        .arg(clap::Arg::with_name("staging")
+             .long("staging")
+             .help("Moves deleted resources into a staging directory so that deletions can be undone until exit"))
+        .arg(clap::Arg::with_name("grace-period")
+             .long("grace-period")
+             .takes_value(true)
+             .value_name("SECONDS")
+             .requires("staging")
+             .help("Purges staged resources after this many seconds instead of at exit"))
+        .arg(cleaner_arg().conflicts_with("staging"))
+        .arg(wrappers_arg())
+        .arg(clap::Arg::with_name("sweep-days")
+             .long("sweep-days")
+             .takes_value(true)
+             .value_name("DAYS")
+             .help("Makes sweeping a Cargo target directory remove artifacts not rebuilt for this many days (defaults to 30)"))
+        .arg(clap::Arg::with_name("no-global-caches")
+             .long("no-global-caches")
+             .help("Skips the caches that cargo, npm, pip, gradle and maven keep in the home directory"))
+        .subcommand(clap::SubCommand::with_name("history")
+                    .about("Lists past deletions"))
+        .subcommand(clap::SubCommand::with_name("clean")
+                    .about("Deletes ignored resources without opening the TUI")
+                    .arg(clap::Arg::with_name("DIR")
+                         .help("Sets the root directories to start searching")
+                         .multiple(true)
+                         .index(1))
+                    .arg(clap::Arg::with_name("only-regenerable")
+                         .long("only-regenerable")
+                         .help("Deletes only resources that a build tool can recreate"))
+                    .arg(clap::Arg::with_name("all")
+                         .long("all")
+                         .help("Deletes all ignored resources in the repositories, including ones that can't be recreated, but never the global caches; needs --yes unless --dry-run is given"))
+                    .arg(clap::Arg::with_name("yes")
+                         .long("yes")
+                         .help("Confirms deleting with --all"))
+                    .group(clap::ArgGroup::with_name("selection")
+                           .args(&["only-regenerable", "all"])
+                           .required(true))
+                    .arg(cleaner_arg())
+                    .arg(wrappers_arg())
+                    .arg(clap::Arg::with_name("dry-run")
+                         .long("dry-run")
+                         .help("Lists what would be deleted without deleting anything")))
+        .subcommand(clap::SubCommand::with_name("list")
+                    .about("Lists repositories and their ignored resources")
+                    .arg(clap::Arg::with_name("DIR")
+                         .help("Sets the root directories to start searching")
+                         .multiple(true)
+                         .index(1))
+                    .arg(clap::Arg::with_name("json")
+                         .long("json")
+                         .help("Prints the scan as JSON, which `rpcl diff` can compare against later"))
+                    .arg(clap::Arg::with_name("save")
+                         .long("save")
+                         .help("Saves the scan as the one `rpcl diff` compares against by default, replacing the previously saved one")))
+        .subcommand(clap::SubCommand::with_name("diff")
+                    .about("Shows how repositories and ignored resources changed since a previous scan")
+                    .arg(clap::Arg::with_name("OLD")
+                         .help("Sets the JSON scan to compare against (defaults to the one saved by the last `rpcl list --save`)")
+                         .index(1))
+                    .arg(clap::Arg::with_name("DIR")
+                         .help("Sets the root directories to start searching (defaults to those of the old scan)")
+                         .multiple(true)
+                         .index(2)))
+        .subcommand(clap::SubCommand::with_name("report")
+                    .about("Writes a report of the ignored resources as HTML or Markdown")
+                    .arg(clap::Arg::with_name("DIR")
+                         .help("Sets the root directories to start searching")
+                         .multiple(true)
+                         .index(1))
+                    .arg(clap::Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(report::FORMAT_NAMES)
+                         .default_value("md")
+                         .help("Sets the format of the report"))
+                    .arg(clap::Arg::with_name("output")
+                         .long("output")
+                         .short("o")
+                         .takes_value(true)
+                         .value_name("FILE")
+                         .help("Writes the report to FILE instead of the standard output"))
+                    .arg(clap::Arg::with_name("stale-days")
+                         .long("stale-days")
+                         .takes_value(true)
+                         .value_name("DAYS")
+                         .default_value("90")
+                         .help("Lists repositories without git activity for this many days as stale")))
+        .subcommand(clap::SubCommand::with_name("duplicates")
+                    .about("Reports files duplicated across ignored resources, e.g. the same packages in many node_modules")
+                    .arg(clap::Arg::with_name("DIR")
+                         .help("Sets the root directories to start searching")
+                         .multiple(true)
+                         .index(1))
+                    .arg(clap::Arg::with_name("top")
+                         .long("top")
+                         .takes_value(true)
+                         .value_name("N")
+                         .default_value("10")
+                         .help("Sets how many ignored resources and duplicated files to list")))
         .get_matches();
-    let root_path = matches.value_of("DIR").unwrap_or(".").to_owned();
+    let audit_log = AuditLog::default_location();
+
+    // Subcommands take precedence over directories of the same name, which need a path like ./history
+    if let Some(name) = matches.subcommand_name() {
+        if Path::new(name).is_dir() {
+            eprintln!("Running the {} subcommand. To search under the directory named {}, run `rpcl ./{}`", name, name, name);
+        }
+    }
+
+    if matches.subcommand_matches("history").is_some() {
+        return print_history(audit_log.ok_or("Cannot locate the history file")?);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("clean") {
+        if matches.is_present("all") && !matches.is_present("yes") && !matches.is_present("dry-run") {
+            return Err("`clean --all` deletes every ignored resource. Pass --yes to confirm, or --dry-run to list them first".into());
+        }
+        let root_paths = root_paths(matches);
+        let mut repositories = RepositoryStore::new().with_cleaners(cleaners(matches)?);
+        if let Some(audit_log) = audit_log {
+            repositories = repositories.with_audit_log(audit_log);
+        }
+        // The global caches are shared by every project, so they're left out even with --all
+        let repositories = Scanner::new().with_roots(root_paths).with_store(repositories).scan()?;
+        return clean(repositories, matches.is_present("only-regenerable"), matches.is_present("dry-run"));
+    }
 
-    let (tx, rx) = channel();
-    let repositories = RepositoryStore::new();
-    {
-        let repositories = repositories.clone();
-        let root_path = root_path.clone();
-        let _collector = thread::spawn(|| {
-            collect_repositories(root_path, repositories, tx).unwrap();
-        });
+    if let Some(matches) = matches.subcommand_matches("list") {
+        let root_paths = absolute_root_paths(root_paths(matches));
+        let snapshot = scan(&root_paths)?;
+        if matches.is_present("json") {
+            println!("{}", snapshot.to_json()?);
+        } else {
+            print_snapshot(&snapshot);
+        }
+        if matches.is_present("save") {
+            snapshot.save(&Snapshot::cache_location().ok_or("Cannot locate where to save the scan")?)?;
+        }
+        return Ok(());
     }
 
-    run_tui(repositories, root_path, rx)?;
+    if let Some(matches) = matches.subcommand_matches("diff") {
+        let old_path = match matches.value_of("OLD") {
+            Some(path) => std::path::PathBuf::from(path),
+            None => {
+                let path = Snapshot::cache_location().ok_or("Cannot locate the saved scan")?;
+                if !path.exists() {
+                    return Err("No saved scan to compare against. Run `rpcl list --save` first, or give a JSON scan".into());
+                }
+                path
+            },
+        };
+        let old = Snapshot::load(&old_path).map_err(|e| format!("Cannot read {}: {}", old_path.to_string_lossy(), e))?;
+        let root_paths = match matches.values_of("DIR") {
+            Some(_) => absolute_root_paths(root_paths(matches)),
+            None => old.roots.clone(),
+        };
+        let new = scan(&root_paths)?;
+        print_diff(&old, &new);
+        return Ok(());
+    }
+
+    if let Some(matches) = matches.subcommand_matches("report") {
+        let repositories = Scanner::new().with_roots(root_paths(matches)).scan()?;
+        let format = report::Format::from_name(matches.value_of("format").unwrap_or("md")).ok_or("Unknown report format")?;
+        let stale_age = Duration::from_secs(matches.value_of("stale-days").unwrap_or("90").parse::<u64>()? * 24 * 60 * 60);
+        let report = report::render(&repositories.repositories()?, format, stale_age);
+        match matches.value_of("output") {
+            Some(path) => std::fs::write(path, report)?,
+            None => print!("{}", report),
+        }
+        return Ok(());
+    }
+
+    if let Some(matches) = matches.subcommand_matches("duplicates") {
+        let repositories = Scanner::new().with_roots(root_paths(matches)).scan()?;
+        return print_duplicates(&repositories, matches.value_of("top").unwrap_or("10").parse()?);
+    }
+
+    let root_paths = root_paths(&matches);
+    // A file given explicitly has to exist, the default one doesn't
+    let config_path = matches.value_of("config").map(PathBuf::from).or_else(Config::location);
+    let config = match (matches.value_of("config"), &config_path) {
+        (Some(path), _) => Config::load(Path::new(path))?,
+        (None, Some(path)) => Config::load_if_exists(path)?,
+        (None, None) => Config::default(),
+    };
+    let theme = Theme::detect(matches.value_of("theme"));
+    let path_display = matches.value_of("path-display").and_then(PathDisplayMode::from_name).unwrap_or(PathDisplayMode::Full);
+    let overflow = matches.value_of("long-paths").and_then(Overflow::from_name).unwrap_or(Overflow::Scroll);
+
+    let mut repositories = RepositoryStore::new().with_cleaners(cleaners(&matches)?);
+    if let Some(days) = matches.value_of("sweep-days") {
+        repositories = repositories.with_sweep_age(Duration::from_secs(days.parse::<u64>()? * 24 * 60 * 60));
+    }
+    if matches.is_present("staging") {
+        let grace_period = match matches.value_of("grace-period") {
+            Some(seconds) => Some(Duration::from_secs(seconds.parse()?)),
+            None => None,
+        };
+        repositories = repositories.with_staging(Staging::new(grace_period));
+    }
+    if let Some(audit_log) = audit_log {
+        repositories = repositories.with_audit_log(audit_log);
+    }
+    let scanner = Scanner::new()
+        .with_roots(root_paths.clone())
+        .with_store(repositories.clone())
+        .with_global_caches(!matches.is_present("no-global-caches"));
+
+    let settings = Settings {
+        theme,
+        overflow,
+        path_display: PathDisplay::new(path_display),
+        keymap: Keymap::from_config(&config.keys)?,
+        config_path,
+    };
+    // Staged resources are deleted however the TUI exits
+    let _purge_guard = repositories.staging().map(|staging| staging.purge_on_drop());
+    run_tui(&scanner, settings)?;
 
     Ok(())
 }
 
+fn cleaner_arg() -> clap::Arg<'static, 'static> {
+    clap::Arg::with_name("cleaner")
+        .long("cleaner")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .value_name("KIND=STRATEGY")
+        .help(CLEANER_HELP)
+}
+
+const CLEANER_HELP: &str = "Sets how a kind of artifact (rust, node, python, build or other) is cleaned: \
+                            `remove` deletes it and `tool` runs `cargo clean`, `gradle clean` or `dotnet clean` where applicable, \
+                            falling back to deletion. node_modules is only deleted if `npm ci --dry-run` succeeds. \
+                            Can be given multiple times, e.g. --cleaner rust=tool. Can't be combined with --staging, \
+                            which moves resources aside instead of cleaning them";
+
+fn wrappers_arg() -> clap::Arg<'static, 'static> {
+    clap::Arg::with_name("trust-wrappers")
+        .long("trust-wrappers")
+        .help("Lets --cleaner KIND=tool run build tool wrappers that come with the repositories, such as ./gradlew")
+}
 
+fn cleaners(matches: &clap::ArgMatches) -> Result<Cleaners, Box<dyn Error>> {
+    let mut cleaners = Cleaners::new().with_wrappers(matches.is_present("trust-wrappers"));
+    for spec in matches.values_of("cleaner").into_iter().flatten() {
+        cleaners.set(spec)?;
+    }
+    Ok(cleaners)
+}
+
+/// Returns the root directories given on the command line, without duplicates.
+fn root_paths(matches: &clap::ArgMatches) -> Vec<String> {
+    let root_paths = match matches.values_of("DIR") {
+        Some(values) => values.map(|v| v.to_owned()).collect::<Vec<_>>(),
+        None => vec![".".to_owned()],
+    };
+    root_paths.iter().enumerate()
+        .filter(|(i, root)| !root_paths[..*i].contains(root))
+        .map(|(_, root)| root.clone())
+        .collect::<Vec<_>>()
+}
+
+/// Resolves relative root directories so that scans from different working directories can be compared.
+fn absolute_root_paths(root_paths: Vec<String>) -> Vec<String> {
+    root_paths.into_iter()
+        .map(|root| std::fs::canonicalize(&root).map(|p| p.to_string_lossy().to_string()).unwrap_or(root))
+        .collect()
+}
+
+/// Scans the root directories without the TUI.
+fn scan(root_paths: &[String]) -> Result<Snapshot, Box<dyn Error>> {
+    let repositories = Scanner::new().with_roots(root_paths.to_vec()).scan()?;
+    Ok(Snapshot::new(root_paths.to_vec(), &repositories.repositories_sorted()?))
+}
+
+fn clean(mut repositories: RepositoryStore, only_regenerable: bool, dry_run: bool) -> Result<(), Box<dyn Error>> {
+    let mut total = 0;
+    for repository in repositories.repositories_sorted()? {
+        for info in repository.ignored_path_infos().iter().filter(|i| !only_regenerable || i.regenerable()) {
+            println!("{:<11}{}", size_str(info.size()), info.path().to_string_lossy());
+            if dry_run {
+                total += info.size();
+            } else {
+                match repositories.clean_ignored_path(&repository, info) {
+                    Ok(warning) => {
+                        if let Some(e) = warning {
+                            eprintln!("Warning: deleted {} but couldn't log it: {}", info.path().to_string_lossy(), e);
+                        }
+                        total += info.size();
+                    },
+                    Err(e) => eprintln!("Failed to delete {}: {}", info.path().to_string_lossy(), e),
+                }
+            }
+        }
+    }
+    if dry_run {
+        println!("{} would be reclaimed", size_str(total).trim());
+    } else {
+        println!("{} reclaimed", size_str(total).trim());
+    }
+    Ok(())
+}
+
+fn print_history(audit_log: AuditLog) -> Result<(), Box<dyn Error>> {
+    let records = audit_log.records()?;
+    for record in records.iter() {
+        let result = match record.error {
+            Some(ref e) => format!("error: {}", e),
+            None => "ok".to_string(),
+        };
+        println!("{}  {:<11}{:<14}{}  {}",
+                 format_timestamp(record.timestamp),
+                 size_str(record.bytes),
+                 record.backend,
+                 record.path.to_string_lossy(),
+                 result);
+    }
+    let summary = audit::summarize(&records);
+    println!("{} deletions ({} failed, {} undone), {} reclaimed in total",
+             summary.deletions,
+             summary.failures,
+             summary.undone,
+             size_str(summary.reclaimed).trim());
+    Ok(())
+}
+
+fn print_duplicates(repositories: &RepositoryStore, top: usize) -> Result<(), Box<dyn Error>> {
+    let report = duplicates::analyze(&repositories.repositories()?);
+    println!("Ignored resources with the most duplicated content:");
+    for (path, bytes) in report.by_ignored_path.iter().take(top) {
+        println!("{:<11}{}", size_str(*bytes), path.to_string_lossy());
+    }
+    println!("Most duplicated files:");
+    for group in report.groups.iter().take(top) {
+        println!("{:<11}{} copies of {}",
+                 size_str(group.wasted()),
+                 group.paths.len(),
+                 group.paths[0].to_string_lossy());
+    }
+    println!("{} could be saved by keeping one copy of each of {} duplicated files",
+             size_str(report.wasted()).trim(),
+             report.groups.len());
+    Ok(())
+}
+
+fn print_snapshot(snapshot: &Snapshot) {
+    for repository in snapshot.repositories.iter().filter(|r| r.size > 0) {
+        println!("{:<11}{}", size_str(repository.size), repository.path.to_string_lossy());
+        for ignored_path in repository.ignored_paths.iter() {
+            println!("  {:<11}{:<8}{}", size_str(ignored_path.size), ignored_path.kind, ignored_path.path.to_string_lossy());
+        }
+    }
+}
+
+fn print_diff(old: &Snapshot, new: &Snapshot) {
+    let changes = snapshot::diff(old, new);
+    for change in changes.iter() {
+        let sign = if change.delta() < 0 { "-" } else { "+" };
+        println!("{}{:<11}{:<12}{}{}",
+                 sign,
+                 size_str(change.delta().unsigned_abs()).trim_start(),
+                 change.description(),
+                 change.path.to_string_lossy(),
+                 if change.repository { " (repository)" } else { "" });
+    }
+    let total = |snapshot: &Snapshot| snapshot.repositories.iter().map(|r| r.size).sum::<u64>() as i64;
+    let delta = total(new) - total(old);
+    println!("{} changes since {} UTC, {}{} in total",
+             changes.len(),
+             format_timestamp(old.timestamp),
+             if delta < 0 { "-" } else { "+" },
+             size_str(delta.unsigned_abs()).trim());
+}
@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rpcl::audit::format_timestamp;
+use rpcl::{ArtifactKind, IgnoredPathInfo, Repository};
+use crate::format::size_str;
+
+pub const FORMAT_NAMES: &[&str] = &["html", "md"];
+
+/// How many ignored paths the report lists.
+const TOP_IGNORED_PATHS: usize = 20;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Html,
+    Markdown,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "html" => Some(Format::Html),
+            "md" => Some(Format::Markdown),
+            _ => None,
+        }
+    }
+}
+
+/// A table of the report, rendered the same way in every format.
+struct Table {
+    title: String,
+    header: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+}
+
+/// When a repository was last worked on, judging from the files git touches on commits, checkouts and fetches.
+pub fn last_activity(repository: &Path) -> Option<SystemTime> {
+    let git_dir = repository.join(".git");
+    ["index", "HEAD", "FETCH_HEAD", "logs/HEAD"].iter()
+        .filter_map(|f| std::fs::metadata(git_dir.join(f)).and_then(|m| m.modified()).ok())
+        .max()
+}
+
+/// Renders the report of the scanned repositories. Repositories without activity for `stale_age` are listed as stale.
+pub fn render(repositories: &[Repository], format: Format, stale_age: Duration) -> String {
+    let now = SystemTime::now();
+    let mut tables = Vec::new();
+
+    let mut repositories = repositories.iter().filter(|r| r.size() > 0).collect::<Vec<_>>();
+    repositories.sort_by_key(|r| std::cmp::Reverse(r.size()));
+    tables.push(Table {
+        title: "Repositories".to_owned(),
+        header: vec!["Size", "Ignored paths", "Repository"],
+        rows: repositories.iter()
+            .map(|r| vec![size_str(r.size()).trim().to_owned(), r.ignored_path_infos().len().to_string(), r.path().to_string_lossy().to_string()])
+            .collect(),
+    });
+
+    let mut infos = repositories.iter().flat_map(|r| r.ignored_path_infos().iter()).collect::<Vec<&IgnoredPathInfo>>();
+    infos.sort_by_key(|i| std::cmp::Reverse(i.size()));
+    tables.push(Table {
+        title: format!("Top {} ignored paths", TOP_IGNORED_PATHS),
+        header: vec!["Size", "Kind", "Regenerable", "Path"],
+        rows: infos.iter().take(TOP_IGNORED_PATHS)
+            .map(|i| vec![
+                size_str(i.size()).trim().to_owned(),
+                i.kind().name().to_owned(),
+                if i.regenerable() { "yes" } else { "no" }.to_owned(),
+                i.path().to_string_lossy().to_string(),
+            ])
+            .collect(),
+    });
+
+    let mut kinds: HashMap<ArtifactKind, (usize, u64)> = HashMap::new();
+    for info in infos.iter() {
+        let total = kinds.entry(info.kind()).or_default();
+        total.0 += 1;
+        total.1 += info.size();
+    }
+    let mut kinds = kinds.into_iter().collect::<Vec<_>>();
+    kinds.sort_by_key(|(_, (_, size))| std::cmp::Reverse(*size));
+    tables.push(Table {
+        title: "Totals per kind".to_owned(),
+        header: vec!["Size", "Paths", "Kind"],
+        rows: kinds.iter()
+            .map(|(kind, (count, size))| vec![size_str(*size).trim().to_owned(), count.to_string(), kind.name().to_owned()])
+            .collect(),
+    });
+
+    let stale = repositories.iter()
+        .filter(|r| !r.is_global())
+        .filter_map(|r| last_activity(r.path()).map(|t| (r, t)))
+        .filter(|(_, t)| now.duration_since(*t).map(|age| age > stale_age).unwrap_or(false))
+        .collect::<Vec<_>>();
+    tables.push(Table {
+        title: format!("Stale repositories (no activity for {} days)", stale_age.as_secs() / 86400),
+        header: vec!["Size", "Last activity", "Repository"],
+        rows: stale.iter()
+            .map(|(r, t)| vec![size_str(r.size()).trim().to_owned(), format_timestamp(seconds(*t)), r.path().to_string_lossy().to_string()])
+            .collect(),
+    });
+
+    let total = repositories.iter().map(|r| r.size()).sum::<u64>();
+    let summary = format!("{} reclaimable in {} repositories, generated {} UTC",
+                          size_str(total).trim(), repositories.len(), format_timestamp(seconds(now)));
+    match format {
+        Format::Html => render_html(&summary, &tables),
+        Format::Markdown => render_markdown(&summary, &tables),
+    }
+}
+
+fn seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn render_markdown(summary: &str, tables: &[Table]) -> String {
+    let mut out = format!("# Disk hygiene report\n\n{}\n", summary);
+    for table in tables {
+        let _ = write!(out, "\n## {}\n\n| {} |\n|{}\n", table.title, table.header.join(" | "), " --- |".repeat(table.header.len()));
+        for row in table.rows.iter() {
+            let cells = row.iter().map(|c| escape_markdown(c)).collect::<Vec<_>>();
+            let _ = writeln!(out, "| {} |", cells.join(" | "));
+        }
+    }
+    out
+}
+
+fn render_html(summary: &str, tables: &[Table]) -> String {
+    let mut out = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Disk hygiene report</title>\n\
+                                <style>body{font-family:sans-serif}table{border-collapse:collapse}\
+                                td,th{border:1px solid #ccc;padding:2px 8px;text-align:left}</style>\n</head>\n<body>\n");
+    let _ = write!(out, "<h1>Disk hygiene report</h1>\n<p>{}</p>\n", escape_html(summary));
+    for table in tables {
+        let _ = write!(out, "<h2>{}</h2>\n<table>\n<tr>", escape_html(&table.title));
+        for cell in table.header.iter() {
+            let _ = write!(out, "<th>{}</th>", escape_html(cell));
+        }
+        out.push_str("</tr>\n");
+        for row in table.rows.iter() {
+            out.push_str("<tr>");
+            for cell in row.iter() {
+                let _ = write!(out, "<td>{}</td>", escape_html(cell));
+            }
+            out.push_str("</tr>\n");
+        }
+        out.push_str("</table>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// Escapes the characters that would end a table cell or start emphasis, code, a link or inline HTML.
+fn escape_markdown(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '\\' | '|' | '*' | '_' | '`' | '[' | ']' | '<') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_render() {
+        let repositories = vec![
+            Repository::new(PathBuf::from("/src/a"), ".".to_owned(), vec![
+                IgnoredPathInfo::new(PathBuf::from("/src/a/target"), 2048, true),
+                IgnoredPathInfo::new(PathBuf::from("/src/a/a|b"), 1024, false),
+            ]),
+            Repository::new(PathBuf::from("/src/<b>"), ".".to_owned(), vec![
+                IgnoredPathInfo::new(PathBuf::from("/src/<b>/node_modules"), 4096, true),
+            ]),
+        ];
+        let markdown = render(&repositories, Format::Markdown, Duration::from_secs(86400));
+        assert!(markdown.contains("| 4.0 KiB | 1 | /src/\\<b> |"));
+        assert!(markdown.contains("| 2.0 KiB | rust | yes | /src/a/target |"));
+        assert!(markdown.contains("| 1.0 KiB | other | no | /src/a/a\\|b |"));
+        assert!(markdown.contains("| 2.0 KiB | 1 | rust |"));
+        assert_eq!(escape_markdown("a_b/*c*/`d`/[e](f)/<g>/h\\i"), "a\\_b/\\*c\\*/\\`d\\`/\\[e\\](f)/\\<g>/h\\\\i");
+        let html = render(&repositories, Format::Html, Duration::from_secs(86400));
+        assert!(html.contains("<td>/src/&lt;b&gt;</td>"));
+        assert!(!html.contains("<b>"));
+    }
+}
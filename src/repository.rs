@@ -1,17 +1,85 @@
-use std::error::Error;
 use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
 use std::path::{Path, PathBuf};
 
+use crate::error::{Error, Result};
+use crate::audit::{AuditLog, Record, UNDO_BACKEND};
+use crate::cleaner::Cleaners;
+use crate::collector::GLOBAL_CACHES_ROOT;
+use crate::staging::Staging;
+use crate::sweep::{self, SweepReport};
+
+/// Cargo artifacts older than this are swept by default.
+const DEFAULT_SWEEP_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// What kind of artifact an ignored path is, judging from its name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ArtifactKind {
+    Rust,
+    Node,
+    Python,
+    Build,
+    Other,
+}
+
+impl ArtifactKind {
+    pub fn of(path: &Path) -> Self {
+        match path.file_name().and_then(|n| n.to_str()) {
+            Some("target") => ArtifactKind::Rust,
+            Some("node_modules") | Some(".next") | Some(".parcel-cache") => ArtifactKind::Node,
+            Some(".venv") | Some("venv") | Some("__pycache__") | Some(".tox") | Some(".mypy_cache") | Some(".pytest_cache") => ArtifactKind::Python,
+            Some("build") | Some("dist") | Some("out") | Some("bin") | Some("obj") | Some(".gradle") => ArtifactKind::Build,
+            _ => ArtifactKind::Other,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "rust" => Some(ArtifactKind::Rust),
+            "node" => Some(ArtifactKind::Node),
+            "python" => Some(ArtifactKind::Python),
+            "build" => Some(ArtifactKind::Build),
+            "other" => Some(ArtifactKind::Other),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ArtifactKind::Rust => "rust",
+            ArtifactKind::Node => "node",
+            ArtifactKind::Python => "python",
+            ArtifactKind::Build => "build",
+            ArtifactKind::Other => "other",
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct IgnoredPathInfo {
     path: PathBuf,
     size: u64,
+    /// Whether a build tool can recreate the path, so that it's safe to delete
+    regenerable: bool,
+    /// Whether this is a Cargo `target` directory that can be swept
+    sweepable: bool,
+    /// What a sweep would reclaim, once planned
+    sweep: Option<SweepReport>,
 }
 
 impl IgnoredPathInfo {
-    pub fn new(path: PathBuf, size: u64) -> Self {
+    pub fn new(path: PathBuf, size: u64, regenerable: bool) -> Self {
         Self {
-            path, size
+            path, size, regenerable, sweepable: false, sweep: None
+        }
+    }
+
+    /// Marks the path as a Cargo `target` directory whose sweep gets planned when its details are shown.
+    pub fn with_sweepable(self, sweepable: bool) -> Self {
+        Self {
+            sweepable,
+            ..self
         }
     }
     
@@ -22,31 +90,94 @@ impl IgnoredPathInfo {
     pub fn size(&self) -> u64 {
         self.size
     }
+
+    pub fn regenerable(&self) -> bool {
+        self.regenerable
+    }
+
+    pub fn is_sweepable(&self) -> bool {
+        self.sweepable
+    }
+
+    /// What a sweep would reclaim, if it has been planned.
+    pub fn sweep(&self) -> Option<SweepReport> {
+        self.sweep
+    }
+
+    pub fn kind(&self) -> ArtifactKind {
+        ArtifactKind::of(self.path())
+    }
 }
 
-impl IgnoredPathInfo {
-    fn clean(&mut self) -> Result<(), Box<dyn Error>> {
-        std::fs::remove_dir_all(self.path.as_path())?;
-        self.size = 0;
-        Ok(())
+/// An entry that couldn't be read while scanning a repository, which was scanned anyway.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Warning {
+    pub path: PathBuf,
+    pub category: &'static str,
+    pub message: String,
+}
+
+impl Warning {
+    pub fn new(path: PathBuf, error: &Error) -> Self {
+        Self {
+            path,
+            category: error.category(),
+            message: error.to_string(),
+        }
     }
 }
 
 #[derive(Clone)]
 pub struct Repository {
     path: PathBuf,
+    /// The path with symlinks resolved, used to tell whether two paths are the same repository
+    canonical_path: PathBuf,
+    /// The root directory the repository was found under
+    root: String,
     ignored_path_infos: Vec<IgnoredPathInfo>,
+    /// Whether this holds the global caches of build tools instead of being a repository
+    global: bool,
+    warnings: Vec<Warning>,
 }
 
 impl Repository {
-    pub fn new(path: PathBuf, ignored_path_infos: Vec<IgnoredPathInfo>) -> Self {
-        Self { path, ignored_path_infos }
+    pub fn new(path: PathBuf, root: String, ignored_path_infos: Vec<IgnoredPathInfo>) -> Self {
+        let canonical_path = canonicalize(&path);
+        Self { path, canonical_path, root, ignored_path_infos, global: false, warnings: Vec::new() }
+    }
+
+    pub fn with_warnings(self, warnings: Vec<Warning>) -> Self {
+        Self {
+            warnings,
+            ..self
+        }
+    }
+
+    /// Creates the pseudo repository holding the global caches found under `home`.
+    pub fn global_caches(home: PathBuf, ignored_path_infos: Vec<IgnoredPathInfo>) -> Self {
+        Self {
+            global: true,
+            ..Self::new(home, GLOBAL_CACHES_ROOT.to_owned(), ignored_path_infos)
+        }
+    }
+
+    pub fn is_global(&self) -> bool {
+        self.global
+    }
+
+    /// Tells the entries apart by the global flag too, as the home directory may be a repository itself.
+    fn is_same(&self, other: &Repository) -> bool {
+        self.path == other.path && self.global == other.global
     }
 
     pub fn path(&self) -> &Path {
         self.path.as_path()
     }
 
+    pub fn root(&self) -> &str {
+        &self.root
+    }
+
     pub fn size(&self) -> u64 {
         self.ignored_path_infos.iter().fold(0, |acc, i| acc + i.size())
     }
@@ -55,64 +186,329 @@ impl Repository {
         &self.ignored_path_infos
     }
 
-    fn clean_ignored_path(&mut self, ignored_path_info: &IgnoredPathInfo) -> Result<(), Box<dyn Error>> {
-        for i in self.ignored_path_infos.iter_mut() {
-            if i.path() == ignored_path_info.path() {
-                i.clean()?;
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// Counts the warnings by category, e.g. `permission denied: 3 entries`.
+    pub fn warning_summary(&self) -> String {
+        let mut counts: Vec<(&str, usize)> = Vec::new();
+        for warning in self.warnings.iter() {
+            match counts.iter_mut().find(|(category, _)| *category == warning.category) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((warning.category, 1)),
             }
         }
-        self.ignored_path_infos = self.ignored_path_infos.iter()
-            .filter(|i| i.size() != 0)
-            .map(|i| i.clone())
-            .collect::<Vec<_>>();
-        Ok(())
+        counts.iter()
+            .map(|(category, count)| format!("{}: {} {}", category, count, if *count == 1 { "entry" } else { "entries" }))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Forgets an ignored path once it has been cleaned.
+    fn remove_ignored_path(&mut self, path: &Path) {
+        self.ignored_path_infos.retain(|i| i.path() != path);
     }
 }
 
 #[derive(Clone)]
 pub struct RepositoryStore {
     store: Arc<RwLock<Vec<Repository>>>,
+    /// Bytes freed by cleaning since the store was created
+    reclaimed: Arc<AtomicU64>,
+    staging: Option<Staging>,
+    audit_log: Option<AuditLog>,
+    cleaners: Cleaners,
+    sweep_age: Duration,
 }
 
 impl RepositoryStore {
     pub fn new() -> Self {
         Self {
             store: Arc::new(RwLock::new(Vec::new())),
+            reclaimed: Arc::new(AtomicU64::new(0)),
+            staging: None,
+            audit_log: None,
+            cleaners: Cleaners::new(),
+            sweep_age: DEFAULT_SWEEP_AGE,
         }
     }
 
-    //FIXME: return an Error instead of panic!
-    pub fn add(&self, repository: Repository) -> Result<(), Box<dyn Error>> {
-        self.store.clone().write().expect("RwLock Error").push(repository);
-        Ok(())
+    /// Creates a store whose deletions go through `staging` and can be undone.
+    /// Staged paths are moved aside rather than cleaned, so the strategies of `with_cleaners` don't apply.
+    pub fn with_staging(self, staging: Staging) -> Self {
+        Self {
+            staging: Some(staging),
+            ..self
+        }
+    }
+
+    /// Makes the store clean each kind of artifact with the strategy set in `cleaners`.
+    pub fn with_cleaners(self, cleaners: Cleaners) -> Self {
+        Self {
+            cleaners,
+            ..self
+        }
+    }
+
+    /// Makes sweeps remove Cargo artifacts that haven't been rebuilt for `sweep_age`.
+    pub fn with_sweep_age(self, sweep_age: Duration) -> Self {
+        Self {
+            sweep_age,
+            ..self
+        }
+    }
+
+    pub fn sweep_age(&self) -> Duration {
+        self.sweep_age
+    }
+
+    /// Makes the store record every clean operation in `audit_log`.
+    pub fn with_audit_log(self, audit_log: AuditLog) -> Self {
+        Self {
+            audit_log: Some(audit_log),
+            ..self
+        }
+    }
+
+    pub fn staging(&self) -> Option<&Staging> {
+        self.staging.as_ref()
+    }
+
+    /// Adds a repository unless the same repository was already added
+    /// through another root or a symlink. Returns whether it was added.
+    pub fn add(&self, repository: Repository) -> Result<bool> {
+        let store = self.store.clone();
+        let mut store = store.write()?;
+        if store.iter().any(|r| r.canonical_path == repository.canonical_path && r.global == repository.global) {
+            return Ok(false);
+        }
+        store.push(repository);
+        Ok(true)
     }
 
-    //FIXME: return an Error instead of panic!
-    pub fn repositories(&self) -> Result<Vec<Repository>, Box<dyn Error>> {
-        Ok(self.store.clone().read().expect("RwLock Error").clone())
+    pub fn contains(&self, path: &Path) -> Result<bool> {
+        let canonical_path = canonicalize(path);
+        Ok(self.store.clone().read()?.iter().any(|r| !r.global && r.canonical_path == canonical_path))
     }
 
-    pub fn repositories_sorted(&self) -> Result<Vec<Repository>, Box<dyn Error>> {
+    pub fn repositories(&self) -> Result<Vec<Repository>> {
+        Ok(self.store.clone().read()?.clone())
+    }
+
+    pub fn repositories_sorted(&self) -> Result<Vec<Repository>> {
         let mut repos = self.repositories()?;
-        repos.sort_by(|a, b| b.size().cmp(&a.size()));
+        // The global caches are kept at the top as a section of their own
+        repos.sort_by_key(|r| (!r.is_global(), std::cmp::Reverse(r.size())));
         Ok(repos)
     }
 
-    pub fn filtered_len(&self) -> Result<usize, Box<dyn Error>> {
-        Ok(self.store.clone().read().expect("RwLock Error").iter().filter(|repo| repo.size() > 0).count())
+    pub fn len(&self) -> Result<usize> {
+        Ok(self.store.clone().read()?.len())
+    }
+
+    pub fn is_empty(&self) -> Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Total size of ignored paths across all repositories.
+    pub fn total_size(&self) -> Result<u64> {
+        Ok(self.store.clone().read()?.iter().map(|repo| repo.size()).sum())
+    }
+
+    pub fn reclaimed(&self) -> u64 {
+        self.reclaimed.load(Ordering::SeqCst)
+    }
+
+    pub fn filtered_len(&self) -> Result<usize> {
+        Ok(self.store.clone().read()?.iter().filter(|repo| repo.size() > 0).count())
     }
 
-    pub fn find_by_path(&self, path: PathBuf) -> Result<Option<Repository>, Box<dyn Error>> {
-        Ok(self.store.clone().read().expect("RwLock Error").iter().find(|r| r.path().to_path_buf() == path).map(|r| r.clone()))
+    pub fn find_by_path(&self, path: PathBuf, global: bool) -> Result<Option<Repository>> {
+        Ok(self.store.clone().read()?.iter().find(|r| r.path().to_path_buf() == path && r.global == global).cloned())
     }
 
-    pub fn clean_ignored_path(&mut self, repository: &Repository, ignored_path_info: &IgnoredPathInfo) -> Result<(), Box<dyn Error>> {
-        for r in self.store.clone().write().expect("RwLock Error").iter_mut() {
-            if r.path() == repository.path() {
-                r.clean_ignored_path(ignored_path_info)?;
+    /// Cleans an ignored path and logs it. Returns the error of writing the audit log, if any,
+    /// as a warning since the path is cleaned anyway.
+    pub fn clean_ignored_path(&mut self, repository: &Repository, ignored_path_info: &IgnoredPathInfo) -> Result<Option<Error>> {
+        let info = self.store.clone().read()?.iter()
+            .filter(|r| r.is_same(repository))
+            .flat_map(|r| r.ignored_path_infos.iter())
+            .find(|i| i.path() == ignored_path_info.path())
+            .cloned();
+        let info = match info {
+            Some(info) => info,
+            None => return Ok(None),
+        };
+        // Build tools can take a while, so the store stays unlocked meanwhile
+        let result = match self.staging.as_ref() {
+            Some(staging) => staging.stage(repository, &info),
+            None => self.cleaners.clean(info.kind(), info.path()),
+        };
+        if result.is_ok() {
+            for r in self.store.clone().write()?.iter_mut().filter(|r| r.is_same(repository)) {
+                let size = r.size();
+                r.remove_ignored_path(info.path());
+                self.reclaimed.fetch_add(size - r.size(), Ordering::SeqCst);
+            }
+        }
+        let backend = match result {
+            Ok(backend) => backend,
+            _ if self.staging.is_some() => "staging",
+            _ => self.cleaners.strategy(info.kind()).name(),
+        };
+        let warning = self.log(Record::new(
+            repository.path().to_path_buf(),
+            info.path().to_path_buf(),
+            info.size(),
+            backend,
+            result.as_ref().err().map(|e| e.to_string())));
+        result?;
+        Ok(warning)
+    }
+
+    /// Plans the sweeps of the repository's Cargo `target` directories that haven't been planned yet,
+    /// which tells what sweeping them would reclaim.
+    pub fn plan_sweeps(&self, repository: &Repository) -> Result<()> {
+        let paths = match self.find_by_path(repository.path().to_path_buf(), repository.is_global())? {
+            Some(repository) => repository.ignored_path_infos.iter()
+                .filter(|i| i.sweepable && i.sweep.is_none())
+                .map(|i| i.path().to_path_buf())
+                .collect::<Vec<_>>(),
+            None => return Ok(()),
+        };
+        // Planning reads every fingerprint, so the store stays unlocked meanwhile
+        let reports = paths.into_iter()
+            .map(|path| {
+                let report = sweep::plan(&path, self.sweep_age, rustc_version(&path).as_deref()).report;
+                (path, report)
+            })
+            .collect::<Vec<_>>();
+        for r in self.store.clone().write()?.iter_mut().filter(|r| r.is_same(repository)) {
+            for i in r.ignored_path_infos.iter_mut() {
+                if let Some((_, report)) = reports.iter().find(|(path, _)| *path == i.path) {
+                    i.sweep = Some(*report);
+                }
             }
         }
         Ok(())
     }
+
+    /// Sweeps a Cargo `target` directory and logs it. Returns the error of writing the audit log, if any.
+    pub fn sweep_ignored_path(&mut self, repository: &Repository, ignored_path_info: &IgnoredPathInfo) -> Result<Option<Error>> {
+        let info = self.store.clone().read()?.iter()
+            .filter(|r| r.is_same(repository))
+            .flat_map(|r| r.ignored_path_infos.iter())
+            .find(|i| i.path() == ignored_path_info.path() && i.sweepable)
+            .cloned();
+        let info = match info {
+            Some(info) => info,
+            None => return Ok(None),
+        };
+        // Planning reads every fingerprint, so the store stays unlocked meanwhile as when cleaning
+        let plan = sweep::plan(info.path(), self.sweep_age, rustc_version(info.path()).as_deref());
+        let result = sweep::sweep(&plan);
+        if let Ok(removed) = result {
+            self.reclaimed.fetch_add(removed, Ordering::SeqCst);
+            for r in self.store.clone().write()?.iter_mut().filter(|r| r.is_same(repository)) {
+                for i in r.ignored_path_infos.iter_mut().filter(|i| i.path() == info.path()) {
+                    i.size -= std::cmp::min(i.size, removed);
+                    i.sweep = Some(SweepReport::default());
+                }
+            }
+        }
+        let warning = self.log(Record::new(
+            repository.path().to_path_buf(),
+            info.path().to_path_buf(),
+            *result.as_ref().unwrap_or(&0),
+            "sweep",
+            result.as_ref().err().map(|e| e.to_string())));
+        result?;
+        Ok(warning)
+    }
+
+    /// Restores the most recently staged path, if any, and logs it as an undo.
+    /// Returns the error of writing the audit log, if any.
+    pub fn undo(&self) -> Result<Option<Error>> {
+        let staging = match self.staging.as_ref() {
+            Some(staging) => staging,
+            None => return Ok(None),
+        };
+        let staged_path = match staging.undo_last()? {
+            Some(staged_path) => staged_path,
+            None => return Ok(None),
+        };
+        if let Some(r) = self.store.clone().write()?.iter_mut().find(|r| r.path() == staged_path.repository.as_path() && r.global == staged_path.global) {
+            r.ignored_path_infos.push(staged_path.original.clone());
+        }
+        self.reclaimed.fetch_sub(staged_path.original.size(), Ordering::SeqCst);
+        Ok(self.log(Record::new(
+            staged_path.repository.clone(),
+            staged_path.original.path().to_path_buf(),
+            staged_path.original.size(),
+            UNDO_BACKEND,
+            None)))
+    }
+
+    /// Appends `record` to the audit log, if any, and returns the error if that fails.
+    fn log(&self, record: Record) -> Option<Error> {
+        self.audit_log.as_ref().and_then(|audit_log| audit_log.append(&record).err())
+    }
 }
 
+impl Default for RepositoryStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+/// The rustc version that builds the crate owning the `target` directory at `target`.
+fn rustc_version(target: &Path) -> Option<String> {
+    sweep::current_rustc_version(target.parent()?)
+}
+
+fn canonicalize(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_warning_summary() {
+        let denied = Error::from(std::io::Error::from(std::io::ErrorKind::PermissionDenied));
+        let other = Error::from(std::io::Error::from(std::io::ErrorKind::InvalidData));
+        let repository = Repository::new(PathBuf::from("/src/a"), ".".to_owned(), Vec::new());
+        assert_eq!(repository.warning_summary(), "");
+        let repository = repository.with_warnings(vec![
+            Warning::new(PathBuf::from("/src/a/x"), &denied),
+            Warning::new(PathBuf::from("/src/a/y"), &other),
+            Warning::new(PathBuf::from("/src/a/z"), &denied),
+        ]);
+        assert_eq!(repository.warning_summary(), "permission denied: 2 entries, IO error: 1 entry");
+    }
+
+    #[test]
+    fn test_undo_global_cache() {
+        let home = std::env::temp_dir().join(format!("rpcl-undo-test-{}", std::process::id()));
+        let cache = home.join(".npm").join("_cacache");
+        std::fs::create_dir_all(&cache).unwrap();
+        std::fs::create_dir_all(home.join(".git")).unwrap();
+        let info = IgnoredPathInfo::new(cache.clone(), 5, false);
+        // The home directory is a repository of its own as well
+        let mut store = RepositoryStore::new().with_staging(Staging::new(None));
+        store.add(Repository::new(home.clone(), ".".to_owned(), Vec::new())).unwrap();
+        let global = Repository::global_caches(home.clone(), vec![info.clone()]);
+        store.add(global.clone()).unwrap();
+
+        store.clean_ignored_path(&global, &info).unwrap();
+        assert!(!cache.exists());
+        assert!(store.undo().unwrap().is_none());
+        assert!(cache.exists());
+        assert_eq!(store.find_by_path(home.clone(), true).unwrap().unwrap().size(), 5);
+        assert_eq!(store.find_by_path(home.clone(), false).unwrap().unwrap().size(), 0);
+        std::fs::remove_dir_all(&home).unwrap();
+    }
+}
@@ -0,0 +1,114 @@
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+
+use crate::error::Result;
+use crate::collector::{self, Event};
+use crate::repository::RepositoryStore;
+
+/// Scans root directories for repositories and their ignored paths.
+///
+/// Each root directory is walked on a thread of its own, adding repositories to the store as they're found.
+pub struct Scanner {
+    roots: Vec<String>,
+    store: RepositoryStore,
+    global_caches: bool,
+}
+
+impl Scanner {
+    pub fn new() -> Self {
+        Self {
+            roots: Vec::new(),
+            store: RepositoryStore::new(),
+            global_caches: false,
+        }
+    }
+
+    /// Adds root directories to scan, skipping ones already added.
+    pub fn with_roots<I: IntoIterator<Item = S>, S: Into<String>>(mut self, roots: I) -> Self {
+        for root in roots.into_iter().map(|r| r.into()) {
+            if !self.roots.contains(&root) {
+                self.roots.push(root);
+            }
+        }
+        self
+    }
+
+    /// Makes the scanner add repositories to `store`, which may be set up with staging, cleaners and so on.
+    pub fn with_store(self, store: RepositoryStore) -> Self {
+        Self {
+            store,
+            ..self
+        }
+    }
+
+    /// Makes the scanner also look for the caches that build tools keep in the home directory.
+    pub fn with_global_caches(self, global_caches: bool) -> Self {
+        Self {
+            global_caches,
+            ..self
+        }
+    }
+
+    pub fn roots(&self) -> &[String] {
+        &self.roots
+    }
+
+    /// The roots that progress is reported under: the root directories, and `GLOBAL_CACHES_ROOT` if the global caches are looked for.
+    pub fn progress_roots(&self) -> Vec<String> {
+        let mut roots = self.roots.clone();
+        if self.global_caches {
+            roots.push(collector::GLOBAL_CACHES_ROOT.to_owned());
+        }
+        roots
+    }
+
+    pub fn store(&self) -> &RepositoryStore {
+        &self.store
+    }
+
+    /// Starts scanning in the background. The receiver gets a `Done` event for each root directory,
+    /// and one for the global caches if they're looked for.
+    pub fn spawn(&self) -> Receiver<Event> {
+        let (tx, rx) = channel();
+        for root in self.roots.iter() {
+            let repositories = self.store.clone();
+            let root = root.clone();
+            let tx = tx.clone();
+            // A collector only fails once the store is poisoned or the receiver is gone, leaving no one to tell
+            let _collector = thread::spawn(|| {
+                let _ = collector::collect_repositories(root, repositories, tx);
+            });
+        }
+        if self.global_caches {
+            let repositories = self.store.clone();
+            let _collector = thread::spawn(|| {
+                let _ = collector::collect_global_caches(repositories, tx);
+            });
+        }
+        rx
+    }
+
+    /// Scans all root directories and returns the store once done.
+    pub fn scan(self) -> Result<RepositoryStore> {
+        let rx = self.spawn();
+        wait(&rx, self.progress_roots().len())?;
+        Ok(self.store)
+    }
+}
+
+impl Default for Scanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Blocks until `collectors` collectors are done.
+pub fn wait(rx: &Receiver<Event>, collectors: usize) -> Result<()> {
+    let mut done = 0;
+    while done < collectors {
+        if let Event::Done(_) = rx.recv()? {
+            done += 1;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+
+use rpcl::Result;
+use rpcl::Repository;
+
+/// The result of a scan, as written by `rpcl list --json`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    pub roots: Vec<String>,
+    pub repositories: Vec<RepositorySnapshot>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RepositorySnapshot {
+    pub path: PathBuf,
+    pub root: String,
+    pub size: u64,
+    pub ignored_paths: Vec<IgnoredPathSnapshot>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct IgnoredPathSnapshot {
+    pub path: PathBuf,
+    pub size: u64,
+    pub kind: String,
+    pub regenerable: bool,
+}
+
+impl Snapshot {
+    pub fn new(roots: Vec<String>, repositories: &[Repository]) -> Self {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let repositories = repositories.iter()
+            .filter(|r| !r.is_global())
+            .map(|r| RepositorySnapshot {
+                path: r.path().to_path_buf(),
+                root: r.root().to_owned(),
+                size: r.size(),
+                ignored_paths: r.ignored_path_infos().iter()
+                    .map(|i| IgnoredPathSnapshot {
+                        path: i.path().to_path_buf(),
+                        size: i.size(),
+                        kind: i.kind().name().to_owned(),
+                        regenerable: i.regenerable(),
+                    })
+                    .collect(),
+            })
+            .collect();
+        Self { timestamp, roots, repositories }
+    }
+
+    /// The snapshot saved by the last `rpcl list --save` under `$XDG_CACHE_HOME/rpcl`, falling back to `~/.cache/rpcl`.
+    pub fn cache_location() -> Option<PathBuf> {
+        let cache_home = match std::env::var_os("XDG_CACHE_HOME") {
+            Some(dir) if PathBuf::from(&dir).is_absolute() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".cache"),
+        };
+        Some(cache_home.join("rpcl").join("last-scan.json"))
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// A repository or ignored path whose size changed between two snapshots.
+#[derive(Debug, PartialEq)]
+pub struct Change {
+    pub path: PathBuf,
+    /// Whether the path is a repository rather than an ignored path
+    pub repository: bool,
+    /// `None` if the path appeared since the old snapshot
+    pub old: Option<u64>,
+    /// `None` if the path disappeared since the old snapshot
+    pub new: Option<u64>,
+}
+
+impl Change {
+    pub fn delta(&self) -> i64 {
+        self.new.unwrap_or(0) as i64 - self.old.unwrap_or(0) as i64
+    }
+
+    pub fn description(&self) -> &'static str {
+        match (self.old, self.new) {
+            (None, _) => "appeared",
+            (_, None) => "disappeared",
+            _ if self.delta() > 0 => "grew",
+            _ => "shrank",
+        }
+    }
+}
+
+/// Lists the repositories and ignored paths that differ between `old` and `new`, largest change first.
+pub fn diff(old: &Snapshot, new: &Snapshot) -> Vec<Change> {
+    let sizes = |snapshot: &Snapshot| {
+        let mut sizes = HashMap::new();
+        for r in snapshot.repositories.iter() {
+            sizes.insert((r.path.clone(), true), r.size);
+            for i in r.ignored_paths.iter() {
+                sizes.insert((i.path.clone(), false), i.size);
+            }
+        }
+        sizes
+    };
+    let old_sizes = sizes(old);
+    let new_sizes = sizes(new);
+    let mut changes = old_sizes.keys().chain(new_sizes.keys().filter(|k| !old_sizes.contains_key(k)))
+        .map(|key| Change {
+            path: key.0.clone(),
+            repository: key.1,
+            old: old_sizes.get(key).cloned(),
+            new: new_sizes.get(key).cloned(),
+        })
+        .filter(|c| c.old != c.new)
+        .collect::<Vec<_>>();
+    changes.sort_by_key(|c| (std::cmp::Reverse(c.delta().abs()), c.path.clone()));
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rpcl::IgnoredPathInfo;
+
+    #[test]
+    fn test_json_round_trip() {
+        let repositories = vec![Repository::new(PathBuf::from("/src/a"), "/src".to_owned(), vec![
+            IgnoredPathInfo::new(PathBuf::from("/src/a/target"), 2048, true),
+        ])];
+        let snapshot = Snapshot::new(vec!["/src".to_owned()], &repositories);
+        assert_eq!(snapshot.repositories[0].ignored_paths[0].kind, "rust");
+        let json = snapshot.to_json().unwrap();
+        assert_eq!(serde_json::from_str::<Snapshot>(&json).unwrap(), snapshot);
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = Snapshot::new(vec![], &[
+            Repository::new(PathBuf::from("/a"), "/".to_owned(), vec![
+                IgnoredPathInfo::new(PathBuf::from("/a/target"), 100, true),
+                IgnoredPathInfo::new(PathBuf::from("/a/dist"), 10, true),
+            ]),
+        ]);
+        let new = Snapshot::new(vec![], &[
+            Repository::new(PathBuf::from("/a"), "/".to_owned(), vec![
+                IgnoredPathInfo::new(PathBuf::from("/a/target"), 300, true),
+                IgnoredPathInfo::new(PathBuf::from("/a/node_modules"), 5, true),
+            ]),
+        ]);
+        let changes = diff(&old, &new);
+        let summary = changes.iter().map(|c| (c.path.to_str().unwrap(), c.description(), c.delta())).collect::<Vec<_>>();
+        assert_eq!(summary, vec![
+            ("/a/target", "grew", 200),
+            ("/a", "grew", 195),
+            ("/a/dist", "disappeared", -10),
+            ("/a/node_modules", "appeared", 5),
+        ]);
+    }
+}
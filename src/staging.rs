@@ -0,0 +1,226 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::error::{Error, Result};
+use crate::cleaner::remove;
+use crate::repository::{IgnoredPathInfo, Repository};
+
+/// A path that was moved into a staging directory instead of being deleted.
+#[derive(Clone)]
+pub struct StagedPath {
+    /// The path as it was before being staged
+    pub original: IgnoredPathInfo,
+    pub staged: PathBuf,
+    pub repository: PathBuf,
+    /// Whether the path belonged to the global caches entry rather than a repository
+    pub global: bool,
+    pub staged_at: Instant,
+}
+
+struct Inner {
+    /// Staging directories created so far
+    dirs: Vec<PathBuf>,
+    staged: Vec<StagedPath>,
+    counter: usize,
+}
+
+/// Holds deleted paths for a while so that deletions can be undone.
+///
+/// Paths are renamed into a staging directory inside the `.git` directory of
+/// their own repository, so that neither git nor the collector picks it up.
+/// Paths that can't be renamed there, e.g. mount points, are deleted instead.
+#[derive(Clone)]
+pub struct Staging {
+    inner: Arc<Mutex<Inner>>,
+    /// How long staged paths are kept. `None` keeps them until exit.
+    grace_period: Option<Duration>,
+}
+
+impl Staging {
+    pub fn new(grace_period: Option<Duration>) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                dirs: Vec::new(),
+                staged: Vec::new(),
+                counter: 0,
+            })),
+            grace_period,
+        }
+    }
+
+    /// Moves the path into staging. Returns the name of the backend used,
+    /// which is `remove` if the path had to be deleted instead.
+    pub fn stage(&self, repository: &Repository, ignored_path_info: &IgnoredPathInfo) -> Result<&'static str> {
+        let path = ignored_path_info.path();
+        let dir = staging_dir(repository.path());
+        let staged = {
+            let mut inner = self.inner.lock()?;
+            if !inner.dirs.contains(&dir) {
+                std::fs::create_dir_all(&dir)?;
+                inner.dirs.push(dir.clone());
+            }
+            inner.counter += 1;
+            let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            dir.join(format!("{}-{}-{}", std::process::id(), inner.counter, name))
+        };
+        // Unlocked meanwhile, as deleting instead can take a while and the TUI asks for the count on every draw
+        match std::fs::rename(path, &staged) {
+            Ok(()) => {},
+            Err(ref e) if e.kind() == io::ErrorKind::CrossesDevices => {
+                remove(path)?;
+                return Ok("remove");
+            },
+            Err(e) => return Err(e.into()),
+        }
+        self.inner.lock()?.staged.push(StagedPath {
+            original: ignored_path_info.clone(),
+            staged,
+            repository: repository.path().to_path_buf(),
+            global: repository.is_global(),
+            staged_at: Instant::now(),
+        });
+        Ok("staging")
+    }
+
+    /// Moves the most recently staged path back to where it was.
+    pub fn undo_last(&self) -> Result<Option<StagedPath>> {
+        let mut inner = self.inner.lock()?;
+        let staged_path = match inner.staged.pop() {
+            Some(staged_path) => staged_path,
+            None => return Ok(None),
+        };
+        if let Err(e) = std::fs::rename(&staged_path.staged, staged_path.original.path()) {
+            inner.staged.push(staged_path);
+            return Err(e.into());
+        }
+        Ok(Some(staged_path))
+    }
+
+    pub fn grace_period(&self) -> Option<Duration> {
+        self.grace_period
+    }
+
+    pub fn len(&self) -> Result<usize> {
+        Ok(self.inner.lock()?.staged.len())
+    }
+
+    pub fn is_empty(&self) -> Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Deletes staged paths older than the grace period.
+    pub fn purge_expired(&self) -> Result<()> {
+        let grace_period = match self.grace_period {
+            Some(grace_period) => grace_period,
+            None => return Ok(()),
+        };
+        let expired = {
+            let mut inner = self.inner.lock()?;
+            let (expired, kept) = inner.staged.drain(..).partition(|s| s.staged_at.elapsed() >= grace_period);
+            inner.staged = kept;
+            expired
+        };
+        self.purge(expired)
+    }
+
+    /// Deletes all staged paths and removes the staging directories.
+    pub fn purge_all(&self) -> Result<()> {
+        let staged = self.inner.lock()?.staged.drain(..).collect::<Vec<_>>();
+        let result = self.purge(staged);
+        let dirs = self.inner.lock()?.dirs.drain(..).collect::<Vec<_>>();
+        for dir in dirs {
+            // Leave directories still holding data of other rpcl processes
+            let _ = std::fs::remove_dir(dir);
+        }
+        result
+    }
+
+    /// Returns a guard that purges everything staged when dropped, however the program exits.
+    pub fn purge_on_drop(&self) -> PurgeGuard {
+        PurgeGuard { staging: self.clone() }
+    }
+
+    /// Deletes the staged paths, keeping the ones that fail for the next purge.
+    /// Returns the first error.
+    fn purge(&self, staged_paths: Vec<StagedPath>) -> Result<()> {
+        let mut failed = Vec::new();
+        let mut error: Option<Error> = None;
+        for staged_path in staged_paths {
+            if let Err(e) = remove(&staged_path.staged) {
+                error.get_or_insert(e);
+                failed.push(staged_path);
+            }
+        }
+        self.inner.lock()?.staged.extend(failed);
+        match error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Purges a staging area when dropped.
+pub struct PurgeGuard {
+    staging: Staging,
+}
+
+impl Drop for PurgeGuard {
+    fn drop(&mut self) {
+        if let Err(e) = self.staging.purge_all() {
+            eprintln!("Failed to delete staged resources: {}", e);
+        }
+    }
+}
+
+fn staging_dir(repository: &Path) -> PathBuf {
+    let git_dir = repository.join(".git");
+    if git_dir.is_dir() {
+        git_dir.join("rpcl-staging")
+    } else {
+        repository.join(".rpcl-staging")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stage_and_undo() {
+        let repository = std::env::temp_dir().join(format!("rpcl-staging-test-{}", std::process::id()));
+        let target = repository.join("target");
+        std::fs::create_dir_all(repository.join(".git")).unwrap();
+        std::fs::create_dir_all(&target).unwrap();
+        std::fs::write(target.join("file"), "content").unwrap();
+
+        let info = IgnoredPathInfo::new(target.clone(), 7, true);
+        let repository = Repository::new(repository, ".".to_owned(), vec![info.clone()]);
+        let staging = Staging::new(None);
+        staging.stage(&repository, &info).unwrap();
+        assert!(!target.exists());
+        assert_eq!(staging.len().unwrap(), 1);
+        let undone = staging.undo_last().unwrap().unwrap();
+        assert_eq!(undone.original.path(), target.as_path());
+        assert_eq!(std::fs::read_to_string(target.join("file")).unwrap(), "content");
+        assert!(staging.undo_last().unwrap().is_none());
+
+        staging.stage(&repository, &info).unwrap();
+        // Each repository gets a staging directory of its own
+        let other = Repository::new(repository.path().join("other"), ".".to_owned(), Vec::new());
+        let other_target = other.path().join("target");
+        std::fs::create_dir_all(other.path().join(".git")).unwrap();
+        std::fs::create_dir_all(&other_target).unwrap();
+        assert_eq!(staging.stage(&other, &IgnoredPathInfo::new(other_target.clone(), 0, true)).unwrap(), "staging");
+        assert!(other.path().join(".git").join("rpcl-staging").is_dir());
+
+        drop(staging.purge_on_drop());
+        assert!(staging.is_empty().unwrap());
+        assert!(!target.exists());
+        assert!(!other_target.exists());
+        assert!(!repository.path().join(".git").join("rpcl-staging").exists());
+        assert!(!other.path().join(".git").join("rpcl-staging").exists());
+        std::fs::remove_dir_all(repository.path()).unwrap();
+    }
+}
@@ -0,0 +1,285 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use walkdir::WalkDir;
+
+use crate::error::Result;
+use crate::cleaner::remove;
+
+/// How many bytes of a metadata file are searched for the rustc version.
+const METADATA_HEAD: u64 = 1024;
+
+/// Reclaimable bytes in a Cargo `target` directory, by category.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SweepReport {
+    /// Incremental compilation caches
+    pub incremental: u64,
+    /// Artifacts built by another toolchain than the rustc on the `PATH`
+    pub other_toolchain: u64,
+    /// Artifacts that haven't been rebuilt for longer than the maximum age
+    pub outdated: u64,
+}
+
+impl SweepReport {
+    pub fn total(&self) -> u64 {
+        self.incremental + self.other_toolchain + self.outdated
+    }
+}
+
+/// Paths that a sweep of a `target` directory removes, along with their sizes.
+pub struct SweepPlan {
+    pub report: SweepReport,
+    pub paths: Vec<PathBuf>,
+}
+
+/// A compilation unit found in `.fingerprint`, e.g. `serde-0123456789abcdef`.
+struct Unit {
+    hash: String,
+    /// Hash of the rustc version the unit was built with
+    rustc: Option<u64>,
+    /// When the unit was last built, from the files in its fingerprint directory
+    modified: SystemTime,
+}
+
+/// The version of the rustc that builds in `dir` as `rustc -V` prints it, e.g. `rustc 1.40.0 (73528e339 2019-12-16)`.
+/// Runs rustc in `dir`, so that rustup picks the toolchain a `rust-toolchain` file there asks for,
+/// and only the first time for each directory.
+pub fn current_rustc_version(dir: &Path) -> Option<String> {
+    static VERSIONS: Mutex<Option<HashMap<PathBuf, Option<String>>>> = Mutex::new(None);
+    if let Some(version) = VERSIONS.lock().ok()?.get_or_insert_with(HashMap::new).get(dir) {
+        return version.clone();
+    }
+    // Not locked meanwhile, so that other directories don't wait for this rustc
+    let version = rustc_version_in(dir);
+    VERSIONS.lock().ok()?.get_or_insert_with(HashMap::new).insert(dir.to_path_buf(), version.clone());
+    version
+}
+
+fn rustc_version_in(dir: &Path) -> Option<String> {
+    let output = Command::new("rustc").arg("-vV").current_dir(dir).stdin(Stdio::null()).stderr(Stdio::null()).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok()?.lines().next().map(|line| line.to_string())
+}
+
+/// Plans a sweep of the Cargo `target` directory at `target`, which removes the incremental caches
+/// and the artifacts that were built by another toolchain than `rustc_version` or haven't been rebuilt within `max_age`.
+/// Without `rustc_version`, or without any artifacts of that toolchain to tell its units by, no toolchain counts as another.
+pub fn plan(target: &Path, max_age: Duration, rustc_version: Option<&str>) -> SweepPlan {
+    let mut report = SweepReport::default();
+    let mut paths = Vec::new();
+    for profile in profile_dirs(target) {
+        let incremental = profile.join("incremental");
+        if incremental.is_dir() {
+            report.incremental += size_of(&incremental);
+            paths.push(incremental);
+        }
+
+        let units = units(&profile);
+        let unit_paths = unit_paths(&profile);
+        let current_rustc = rustc_version.and_then(|version| current_rustc(&units, &unit_paths, version));
+        let now = SystemTime::now();
+        let mut other_toolchain = HashSet::new();
+        let mut outdated = HashSet::new();
+        for unit in units.iter() {
+            if current_rustc.is_some() && unit.rustc.is_some() && unit.rustc != current_rustc {
+                other_toolchain.insert(unit.hash.as_str());
+            } else if now.duration_since(unit.modified).map(|age| age > max_age).unwrap_or(false) {
+                outdated.insert(unit.hash.as_str());
+            }
+        }
+        for (path, hash) in unit_paths {
+            if other_toolchain.contains(hash.as_str()) {
+                report.other_toolchain += size_of(&path);
+                paths.push(path);
+            } else if outdated.contains(hash.as_str()) {
+                report.outdated += size_of(&path);
+                paths.push(path);
+            }
+        }
+    }
+    SweepPlan { report, paths }
+}
+
+/// Carries out `plan` and returns the number of bytes removed.
+pub fn sweep(plan: &SweepPlan) -> Result<u64> {
+    for path in plan.paths.iter() {
+        remove(path)?;
+    }
+    Ok(plan.report.total())
+}
+
+/// Finds the profile directories, such as `target/debug` or `target/x86_64-unknown-linux-gnu/release`.
+fn profile_dirs(target: &Path) -> Vec<PathBuf> {
+    WalkDir::new(target)
+        .min_depth(1)
+        .max_depth(2)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_dir() && e.path().join(".fingerprint").is_dir())
+        .map(|e| e.into_path())
+        .collect()
+}
+
+fn units(profile: &Path) -> Vec<Unit> {
+    let entries = match std::fs::read_dir(profile.join(".fingerprint")) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let hash = unit_hash(&e.file_name().to_string_lossy())?;
+            let modified = fingerprint_modified(&e.path())?;
+            Some(Unit { hash, rustc: fingerprint_rustc(&e.path()), modified })
+        })
+        .collect()
+}
+
+/// The latest modification time of the files in a fingerprint directory, which cargo rewrites
+/// whenever it builds the unit. The directory's own time only changes when files are added or removed.
+fn fingerprint_modified(dir: &Path) -> Option<SystemTime> {
+    std::fs::read_dir(dir).ok()?
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.metadata().and_then(|m| m.modified()).ok())
+        .max()
+        .or_else(|| std::fs::metadata(dir).and_then(|m| m.modified()).ok())
+}
+
+/// Reads the rustc hash from the JSON files of a fingerprint directory.
+fn fingerprint_rustc(dir: &Path) -> Option<u64> {
+    let entries = std::fs::read_dir(dir).ok()?;
+    entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().extension().map(|e| e == "json").unwrap_or(false))
+        .filter_map(|e| std::fs::read(e.path()).ok())
+        .filter_map(|content| serde_json::from_slice::<serde_json::Value>(&content).ok())
+        .find_map(|fingerprint| fingerprint.get("rustc").and_then(|rustc| rustc.as_u64()))
+}
+
+/// Finds the rustc hash that cargo gave `rustc_version`, from the version rustc writes into the metadata of libraries.
+fn current_rustc(units: &[Unit], unit_paths: &[(PathBuf, String)], rustc_version: &str) -> Option<u64> {
+    // Each rustc hash needs checking once, through any of its units with metadata
+    let mut checked = HashSet::new();
+    for unit in units.iter() {
+        let rustc = match unit.rustc {
+            Some(rustc) if !checked.contains(&rustc) => rustc,
+            _ => continue,
+        };
+        let metadata = unit_paths.iter()
+            .find(|(path, hash)| *hash == unit.hash && path.extension().map(|e| e == "rmeta").unwrap_or(false));
+        if let Some((path, _)) = metadata {
+            if metadata_version_is(path, rustc_version) {
+                return Some(rustc);
+            }
+            checked.insert(rustc);
+        }
+    }
+    None
+}
+
+/// Whether the metadata file at `path` was written by `rustc_version`, which is near its start.
+fn metadata_version_is(path: &Path, rustc_version: &str) -> bool {
+    let mut head = Vec::new();
+    match File::open(path) {
+        Ok(file) => if file.take(METADATA_HEAD).read_to_end(&mut head).is_err() {
+            return false;
+        },
+        Err(_) => return false,
+    }
+    head.windows(rustc_version.len()).any(|window| window == rustc_version.as_bytes())
+}
+
+/// Lists the paths belonging to compilation units in a profile directory along with the unit hashes.
+fn unit_paths(profile: &Path) -> Vec<(PathBuf, String)> {
+    let mut paths = HashMap::new();
+    for dir in [".fingerprint", "build", "deps"].iter() {
+        let entries = match std::fs::read_dir(profile.join(dir)) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.filter_map(|e| e.ok()) {
+            if let Some(hash) = unit_hash(&entry.file_name().to_string_lossy()) {
+                paths.insert(entry.path(), hash);
+            }
+        }
+    }
+    paths.into_iter().collect()
+}
+
+/// Extracts the unit hash from names like `libserde-0123456789abcdef.rlib`.
+fn unit_hash(name: &str) -> Option<String> {
+    let stem = name.split('.').next()?;
+    let hash = stem.rsplit('-').next()?;
+    if hash.len() == 16 && hash.chars().all(|c| c.is_ascii_hexdigit()) && hash.len() < stem.len() {
+        Some(hash.to_string())
+    } else {
+        None
+    }
+}
+
+fn size_of(path: &Path) -> u64 {
+    WalkDir::new(path)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| !e.path_is_symlink())
+        .filter_map(|e| e.metadata().ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unit_hash() {
+        assert_eq!(unit_hash("libserde-0123456789abcdef.rlib"), Some("0123456789abcdef".to_string()));
+        assert_eq!(unit_hash("rpcl-0123456789abcdef"), Some("0123456789abcdef".to_string()));
+        assert_eq!(unit_hash("0123456789abcdef"), None);
+        assert_eq!(unit_hash("rpcl.d"), None);
+    }
+
+    #[test]
+    fn test_plan() {
+        let target = std::env::temp_dir().join(format!("rpcl-sweep-test-{}", std::process::id()));
+        let profile = target.join("debug");
+        let write = |path: PathBuf, content: &str, age: u64| {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, content).unwrap();
+            let modified = SystemTime::now() - Duration::from_secs(age);
+            File::options().write(true).open(&path).unwrap().set_modified(modified).unwrap();
+        };
+        write(profile.join("incremental").join("a").join("x"), "1234", 0);
+        // Built by another toolchain, recently
+        write(profile.join(".fingerprint").join("old-1111111111111111").join("lib-old.json"), "{\"rustc\":1}", 0);
+        write(profile.join("deps").join("libold-1111111111111111.rmeta"), "rust rustc 1.0.0", 0);
+        // Built by the current toolchain, two hours ago
+        write(profile.join(".fingerprint").join("stale-3333333333333333").join("lib-stale.json"), "{\"rustc\": 2}", 7200);
+        write(profile.join("deps").join("libstale-3333333333333333.rlib"), "12345", 7200);
+        // Built by the current toolchain just now
+        write(profile.join(".fingerprint").join("new-2222222222222222").join("lib-new.json"), "{\"path\":\"rustc\",\"rustc\":2}", 0);
+        write(profile.join("deps").join("libnew-2222222222222222.rmeta"), "rust rustc 2.0.0 (abc)", 0);
+
+        let plan = plan(&target, Duration::from_secs(3600), Some("rustc 2.0.0 (abc)"));
+        // Sizes include the directories themselves, like the collector's
+        assert!(plan.report.incremental >= 4);
+        assert!(plan.report.other_toolchain >= 16 + 12);
+        assert!(plan.report.outdated >= 5 + 15);
+        assert_eq!(sweep(&plan).unwrap(), plan.report.total());
+        assert!(!profile.join("deps").join("libold-1111111111111111.rmeta").exists());
+        assert!(!profile.join("deps").join("libstale-3333333333333333.rlib").exists());
+        assert!(profile.join("deps").join("libnew-2222222222222222.rmeta").exists());
+
+        // Without a known toolchain nothing counts as built by another one
+        write(profile.join(".fingerprint").join("old-1111111111111111").join("lib-old.json"), "{\"rustc\":1}", 0);
+        let plan = super::plan(&target, Duration::from_secs(3600), None);
+        assert_eq!(plan.report.other_toolchain, 0);
+        std::fs::remove_dir_all(&target).unwrap();
+    }
+}
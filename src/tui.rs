@@ -1,20 +1,18 @@
-use std::error::Error;
-use std::thread;
-use std::time::Duration;
-use std::sync::mpsc::{channel, Receiver};
-use crossterm::{RawScreen, input, InputEvent, KeyEvent, ClearType, Crossterm};
+use std::path::PathBuf;
+use std::sync::mpsc::channel;
+use crossterm::{ClearType, Crossterm, InputEvent};
 
-use crate::collector;
-use crate::repository::*;
+use rpcl::{Error, Result};
+use rpcl::Scanner;
 
 mod app;
-pub use app::{App, AppState};
+pub use app::{App, AppState, Dirty};
 
 mod list;
 pub use list::List;
 
 mod pathlist;
-pub use pathlist::PathList;
+pub use pathlist::{Overflow, PathList, OVERFLOW_NAMES};
 
 mod usagebar;
 pub use usagebar::UsageBar;
@@ -25,96 +23,205 @@ pub use statusbar::StatusBar;
 mod details;
 pub use details::Details;
 
+mod event;
+use event::{Event, Signal};
+
+mod terminal;
+use terminal::TerminalGuard;
+
 mod helpwindow;
 pub use helpwindow::HelpWindow;
 
 mod window;
 pub use window::Window;
 
-pub fn run_tui(
-    repositories: RepositoryStore,
-    root_path: String,
-    collector_rx: Receiver<collector::Event>,
-) -> Result<(), Box<dyn Error>> {
-    let (spinner_tx, spinner_rx) = channel();
-    let _spinner = thread::spawn(move || {
-        loop {
-            thread::sleep(Duration::from_millis(333));
-            spinner_tx.send(()).unwrap();
-        }
-    });
+mod theme;
+pub use theme::{Theme, THEME_NAMES};
+
+mod summary;
+pub use summary::Summary;
+
+mod layout;
+
+mod text;
 
-    let _raw = RawScreen::into_raw_mode()?;
+mod pathdisplay;
+pub use pathdisplay::{PathDisplay, PathDisplayMode, PATH_DISPLAY_NAMES};
+
+mod keymap;
+pub use keymap::{Action, Keymap};
+
+/// How the TUI shows things and which keys it reacts to.
+pub struct Settings {
+    pub theme: Theme,
+    pub overflow: Overflow,
+    pub path_display: PathDisplay,
+    pub keymap: Keymap,
+    /// Where the config file is, or would be, shown in the help
+    pub config_path: Option<PathBuf>,
+}
+
+/// Ticks between purges of staged paths whose grace period ran out.
+const PURGE_TICKS: usize = 5;
+
+/// Starts `scanner` and shows the repositories it finds as they come in.
+pub fn run_tui(scanner: &Scanner, settings: Settings) -> Result<()> {
+    let repositories = scanner.store();
+    let collector_rx = scanner.spawn();
+    let (tx, rx) = channel();
+    let guard = TerminalGuard::new(tx.clone())?;
+    terminal::forward_signals(tx.clone())?;
+    event::spawn_input(tx.clone());
+    let ticker = event::spawn_ticks(tx.clone());
+    event::forward_collector(collector_rx, tx.clone());
     let crossterm = Crossterm::new();
     let terminal = crossterm.terminal();
     terminal.clear(ClearType::All)?;
-    let cursor = crossterm.cursor();
-    cursor.hide()?;
     let (_width, height) = terminal.size()?;
+    let list_height = layout::list_height(height);
 
     let mut app = App {
         repositories: repositories.clone(),
-        root_path,
         path_list: PathList {
-            list: List {
-                pos: 0,
-                offset: 0,
-                height: height as usize - 2
-            },
+            list: List::new(layout::LIST_TOP, list_height),
             path_scroll_amount: 0,
+            path_scroll_len: 0,
+            show_root: scanner.roots().len() > 1,
+            overflow: settings.overflow,
+            path_display: settings.path_display.clone(),
         },
+        status_bar: StatusBar::new(&scanner.progress_roots()),
         usage_bar: UsageBar,
-        status_bar: StatusBar {
-            spinner_phase: 0,
-            done: false,
-        },
-        details: Details {
-            list: List {
-                pos: 0,
-                offset: 0,
-                height: height as usize - 2,
-            }
-        },
-        help_window: HelpWindow::new(),
+        summary: Summary::new(),
+        details: Details::new(List::new(layout::LIST_TOP, list_height), settings.path_display),
+        help_window: HelpWindow::new(&settings.keymap, settings.config_path),
+        theme: settings.theme,
+        keymap: settings.keymap,
         state: AppState::PathList,
+        events: tx,
+        dirty: Dirty::all(),
     };
+    app.draw()?;
 
-    let input = input();
-    let mut stdin = input.read_async();
-
+    let mut ticks: usize = 0;
+    // Whether expired staged paths are being deleted in the background
+    let mut purging = false;
     loop {
-        let (_width, height) = terminal.size()?;
-        app.path_list.list.height = height as usize - 2;
-        app.details.list.height = height as usize - 2;
-
-        if let Some(event) = stdin.next() {
-            if app.input(event)? {
-                break;
-            }
-        }
-        if let Ok(event) = collector_rx.try_recv() {
+        // Block until something happens, then handle whatever else queued up meanwhile before drawing once
+        let mut event = rx.recv()?;
+        loop {
             match event {
-                collector::Event::Update => {
+                Event::Input(event) => {
+                    if matches!(&event, InputEvent::Keyboard(k) if app.keymap.is(k, Action::Suspend)) {
+                        suspend(&guard, &mut app)?;
+                    } else {
+                        match app.input(event) {
+                            Ok(true) => return Ok(()),
+                            Ok(false) => {},
+                            Err(e) if e.is_recoverable() => {
+                                app.status_bar.show_error(&e);
+                                app.dirty.status_bar = true;
+                            },
+                            Err(e) => return Err(e),
+                        }
+                    }
+                },
+                Event::Collector(event) => {
+                    match event {
+                        rpcl::Event::Update => {
+                            app.dirty.list = true;
+                        },
+                        rpcl::Event::Progress(progress) => {
+                            app.status_bar.update(progress, false);
+                        },
+                        // Counted in the progress, which the status bar shows
+                        rpcl::Event::Skipped(_) => {},
+                        rpcl::Event::Done(progress) => {
+                            app.status_bar.update(progress, true);
+                            if app.status_bar.done() {
+                                app.summary.finish();
+                            }
+                        }
+                    }
+                    app.summary.directories = app.status_bar.total().directories;
+                    app.dirty.summary = true;
+                    app.dirty.status_bar = true;
+                },
+                Event::Tick => {
+                    ticks += 1;
+                    if app.status_bar.tick() {
+                        app.dirty.status_bar = true;
+                    }
+                    if let AppState::PathList = app.state {
+                        if app.path_list.tick() {
+                            app.dirty.list = true;
+                        }
+                    }
+                    if ticks.is_multiple_of(PURGE_TICKS) && !purging {
+                        if let Some(staging) = app.repositories.staging().cloned() {
+                            // Deleting whole trees takes a while, so the TUI keeps drawing meanwhile
+                            purging = true;
+                            let events = app.events.clone();
+                            std::thread::spawn(move || {
+                                let _ = events.send(Event::Purged(staging.purge_expired()));
+                            });
+                        }
+                    }
                 },
-                collector::Event::Done => {
-                    app.status_bar.done = true;
-                }
+                Event::Purged(result) => {
+                    purging = false;
+                    if let Err(e) = result {
+                        app.status_bar.show_error(&e);
+                        app.dirty.status_bar = true;
+                    }
+                    app.dirty.summary = true;
+                    app.dirty.list = true;
+                },
+                Event::Cleaned(result) => {
+                    match result {
+                        Ok(Some(e)) => app.status_bar.show_warning(&format!("Couldn't log the deletion: {}", e)),
+                        Ok(None) => {},
+                        Err(e) => app.status_bar.show_error(&e),
+                    }
+                    app.dirty.summary = true;
+                    app.dirty.list = true;
+                    app.dirty.status_bar = true;
+                },
+                Event::SweepsPlanned(result) => {
+                    if let Err(e) = result {
+                        app.status_bar.show_error(&e);
+                        app.dirty.status_bar = true;
+                    }
+                    app.dirty.list = true;
+                },
+                Event::Signal(Signal::Quit) => return Ok(()),
+                Event::Signal(Signal::Suspend) => suspend(&guard, &mut app)?,
+                Event::Resize => redraw(&mut app)?,
+                Event::Panicked(message) => return Err(Error::Panicked(message)),
             }
-        }
-        if let Ok(_) = spinner_rx.try_recv() {
-            app.status_bar.spinner_phase += 1;
-            app.status_bar.spinner_phase %= 4;
+            event = match rx.try_recv() {
+                Ok(event) => event,
+                Err(_) => break,
+            };
         }
 
-        app.draw()?;
-
-        thread::sleep(Duration::from_millis(67));
-        if app.path_list.path_scroll_amount < 1000 {
-            app.path_list.path_scroll_amount += 1;
+        if app.dirty.any() {
+            app.draw()?;
         }
+        // Drawing tells whether the selected path has to scroll
+        ticker.set_running(app.is_animating()?)?;
     }
+}
 
-    cursor.show()?;
+/// Suspends the process, by a key or by SIGTSTP, and redraws everything once it's resumed,
+/// since the terminal may have been resized or drawn over meanwhile.
+fn suspend(guard: &TerminalGuard, app: &mut App) -> Result<()> {
+    guard.suspend()?;
+    redraw(app)
+}
 
-    Ok(())
+/// Clears the screen and draws everything again at the current terminal size.
+fn redraw(app: &mut App) -> Result<()> {
+    crossterm::terminal().clear(ClearType::All)?;
+    app.resize()
 }
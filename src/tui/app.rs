@@ -1,80 +1,161 @@
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
+use std::sync::mpsc::Sender;
 
-use crossterm::{InputEvent, KeyEvent};
+use crossterm::{ClearType, InputEvent, MouseEvent, MouseButton};
 
-use crate::repository::{Repository, RepositoryStore, IgnoredPathInfo};
+use rpcl::{Error, Result};
+use rpcl::{Repository, RepositoryStore, IgnoredPathInfo};
 use crate::tui::{pathlist, pathlist::PathList};
 use crate::tui::usagebar::UsageBar;
 use crate::tui::statusbar::StatusBar;
 use crate::tui::helpwindow::HelpWindow;
 use crate::tui::{details, details::Details};
+use crate::tui::theme::Theme;
+use crate::tui::summary::Summary;
+use crate::tui::event::Event;
+use crate::tui::{layout, text};
+use crate::tui::keymap::{Action, Keymap};
 
 #[derive(Clone)]
 pub enum AppState {
     PathList,
-    Details(PathBuf),
+    Details {
+        path: PathBuf,
+        /// Whether this is the global caches entry rather than a repository at `path`
+        global: bool,
+    },
+}
+
+/// The regions of the screen that changed since they were last drawn.
+#[derive(Default)]
+pub struct Dirty {
+    pub usage_bar: bool,
+    pub summary: bool,
+    pub list: bool,
+    pub status_bar: bool,
+}
+
+impl Dirty {
+    pub fn all() -> Self {
+        Self { usage_bar: true, summary: true, list: true, status_bar: true }
+    }
+
+    pub fn any(&self) -> bool {
+        self.usage_bar || self.summary || self.list || self.status_bar
+    }
 }
 
 pub struct App {
     pub repositories: RepositoryStore,
-    pub root_path: String,
     pub path_list: PathList,
     pub usage_bar: UsageBar,
     pub status_bar: StatusBar,
+    pub summary: Summary,
     pub details: Details,
     pub help_window: HelpWindow,
+    pub theme: Theme,
+    pub keymap: Keymap,
     pub state: AppState,
+    /// Reports the results of the threads cleaning in the background
+    pub events: Sender<Event>,
+    pub dirty: Dirty,
 }
 
 impl App {
-    pub fn input(&mut self, event: InputEvent) -> Result<bool, Box<dyn std::error::Error>> {
+    /// Handles an input event and marks the regions it changed as dirty. Returns whether to quit.
+    pub fn input(&mut self, event: InputEvent) -> Result<bool> {
         match event.clone() {
             InputEvent::Keyboard(k) => {
-                match k {
-                    KeyEvent::Char(c) => match c {
-                        'q' => {
-                            return Ok(true);
-                        },
-                        'h' => {
-                            self.help_window.show = !self.help_window.show;
-                        },
-                        _ => {},
-                    },
-                    KeyEvent::Ctrl('c') => {
+                // Keys of these actions aren't passed on, so that they work the same everywhere
+                match self.keymap.find(&k, &[Action::Quit, Action::Help, Action::Undo]) {
+                    Some(Action::Quit) => {
                         return Ok(true);
                     },
+                    Some(Action::Help) => {
+                        self.help_window.toggle();
+                        // The help window covers the list, which shows again once it's hidden
+                        self.dirty.list = true;
+                        return Ok(false);
+                    },
+                    Some(Action::Undo) => {
+                        if let Some(e) = self.repositories.undo()? {
+                            self.status_bar.show_warning(&format!("Couldn't log the undo: {}", e));
+                            self.dirty.status_bar = true;
+                        }
+                        self.dirty.summary = true;
+                        self.dirty.list = true;
+                        return Ok(false);
+                    },
                     _ => {},
                 }
-            }
+            },
+            // The usage bar is on the first row
+            InputEvent::Mouse(MouseEvent::Press(MouseButton::Left, x, 1)) => {
+                if let Some(key) = self.usage_bar.key_at(&self.state, &self.keymap, x) {
+                    return self.input(InputEvent::Keyboard(key));
+                }
+                return Ok(false);
+            },
             _ => {},
         }
-        if !self.help_window.show {
+        if self.help_window.show {
+            // The help window takes the movement keys while it's shown
+            if let InputEvent::Keyboard(k) = &event {
+                if self.help_window.input(k, &self.state)? {
+                    self.dirty.list = true;
+                }
+            }
+        } else {
             match self.state {
                 AppState::PathList => {
-                    match self.path_list.input(event.clone(), &self.repositories)? {
+                    let view = self.path_list.list.view();
+                    match self.path_list.input(event.clone(), &self.repositories, &self.keymap)? {
                         Some(pathlist::Event::Open(repository)) => {
-                            self.state = AppState::Details(repository.path().to_path_buf());
+                            self.state = AppState::Details { path: repository.path().to_path_buf(), global: repository.is_global() };
+                            self.plan_sweeps(repository);
+                            self.dirty = Dirty::all();
                         },
-                        None => {},
+                        None => self.dirty.list |= self.path_list.list.view() != view,
                     }
                 },
-                AppState::Details(ref path) => {
-                    let repository = self.repositories.find_by_path(path.clone())?.expect("Repository not found");
-                    match self.details.input(event.clone(), &repository)? {
+                AppState::Details { ref path, global } => {
+                    let repository = match self.repositories.find_by_path(path.clone(), global)? {
+                        Some(repository) => repository,
+                        None => {
+                            let path = path.clone();
+                            self.state = AppState::PathList;
+                            self.dirty = Dirty::all();
+                            return Err(Error::NotFound(path));
+                        },
+                    };
+                    let view = (self.details.list.view(), self.details.toggled.len());
+                    let event = self.details.input(event.clone(), &repository, &self.keymap)?;
+                    self.dirty.list |= (self.details.list.view(), self.details.toggled.len()) != view;
+                    match event {
                         Some(details::Event::Close) => {
                             self.state = AppState::PathList;
+                            self.dirty = Dirty::all();
+                        },
+                        Some(details::Event::DeleteSelected(paths)) => {
+                            self.clean_ignored_paths(&repository, &paths)?;
                         },
-                        Some(details::Event::DeleteAll) => {
-                            self.clean_repository(&repository)?;
+                        Some(details::Event::Sweep(path)) => {
+                            let mut repositories = self.repositories.clone();
+                            let events = self.events.clone();
+                            if let Some(info) = repository.ignored_path_infos().iter().find(|i| i.path() == path.as_path()).cloned() {
+                                std::thread::spawn(move || {
+                                    let result = repositories.sweep_ignored_path(&repository, &info);
+                                    let _ = events.send(Event::Cleaned(result));
+                                });
+                            }
                         },
                         Some(details::Event::Delete(path)) => {
-                            self.clean_ignored_path(
-                                repository.clone(),
-                                repository.ignored_path_infos()
+                            let info = repository.ignored_path_infos()
                                 .iter()
                                 .find(|i| i.path() == path.as_path())
-                                .expect("IgnoredPathInfo not found")
-                                .clone())?;
+                                .ok_or(Error::NotFound(path.clone()))?
+                                .clone();
+                            self.clean_ignored_path(repository.clone(), info)?;
                         },
                         None => {},
                     }
@@ -84,43 +165,110 @@ impl App {
         Ok(false)
     }
 
-    pub fn clean_repository(&mut self, repository: &Repository) -> Result<(), Box<dyn std::error::Error>> {
-        for info in repository.ignored_path_infos().iter() {
+    /// Works out in the background what sweeping the repository's Cargo targets would reclaim.
+    fn plan_sweeps(&self, repository: Repository) {
+        if !repository.ignored_path_infos().iter().any(|i| i.is_sweepable() && i.sweep().is_none()) {
+            return;
+        }
+        let repositories = self.repositories.clone();
+        let events = self.events.clone();
+        std::thread::spawn(move || {
+            let _ = events.send(Event::SweepsPlanned(repositories.plan_sweeps(&repository)));
+        });
+    }
+
+    pub fn clean_ignored_paths(&mut self, repository: &Repository, paths: &[PathBuf]) -> Result<()> {
+        for info in repository.ignored_path_infos().iter().filter(|i| paths.iter().any(|p| p == i.path())) {
             self.clean_ignored_path(repository.clone(), info.clone())?;
         }
         Ok(())
     }
 
-    pub fn clean_ignored_path(&mut self, repository: Repository, ignored_path_info: IgnoredPathInfo) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn clean_ignored_path(&mut self, repository: Repository, ignored_path_info: IgnoredPathInfo) -> Result<()> {
         let mut repositories = self.repositories.clone();
+        let events = self.events.clone();
         std::thread::spawn(move || {
-            if let Err(e) = repositories.clean_ignored_path(&repository, &ignored_path_info) {
-                eprintln!("{}", e);
-            }
+            let result = repositories.clean_ignored_path(&repository, &ignored_path_info);
+            let _ = events.send(Event::Cleaned(result));
         });
         Ok(())
     }
 
-    pub fn draw(&self) -> Result<(), Box<dyn std::error::Error>> {
+    /// Whether anything changes over time: the spinner while scanning, an error waiting to expire,
+    /// the path of the selected repository scrolling, or staged paths waiting for their grace period to run out.
+    pub fn is_animating(&self) -> Result<bool> {
+        let scrolling = matches!(self.state, AppState::PathList) && self.path_list.is_scrolling();
+        let purging = match self.repositories.staging() {
+            Some(staging) => staging.grace_period().is_some() && !staging.is_empty()?,
+            None => false,
+        };
+        Ok(!self.status_bar.done() || self.status_bar.error.is_some() || scrolling || purging)
+    }
+
+    /// Fits the lists to the terminal size, keeping the selected rows visible, and redraws everything.
+    pub fn resize(&mut self) -> Result<()> {
+        let (_width, height) = crossterm::terminal().size()?;
+        let list_height = layout::list_height(height);
+        self.path_list.list.height = list_height;
+        let selected = self.path_list.list.selected();
+        self.path_list.list.select(selected, self.repositories.filtered_len()?);
+        self.details.list.height = list_height;
+        if let AppState::Details { ref path, global } = self.state {
+            if let Some(repository) = self.repositories.find_by_path(path.clone(), global)? {
+                let selected = self.details.list.selected();
+                self.details.list.select(selected, Details::row_count(&repository));
+            }
+        }
+        self.dirty = Dirty::all();
+        Ok(())
+    }
+
+    /// Draws the regions marked dirty and marks them clean.
+    pub fn draw(&mut self) -> Result<()> {
         let cursor = crossterm::cursor();
-        cursor.goto(0, 0)?;
-        self.usage_bar.draw(&self.state)?;
-        let repositories = self.repositories.repositories_sorted()?;
-        match self.state {
-            AppState::PathList => {
-                self.path_list.draw(&repositories)?;
-            },
-            AppState::Details(ref path) => {
-                let repository = self.repositories.find_by_path(path.clone())?;
-                if let Some(repository) = repository {
-                    self.details.draw(repository)?;
+        let terminal = crossterm::terminal();
+        let (width, height) = terminal.size()?;
+        if layout::is_too_small(width, height) {
+            let message = format!("Terminal too small (need {}x{})", layout::MIN_WIDTH, layout::MIN_HEIGHT);
+            terminal.clear(ClearType::All)?;
+            cursor.goto(0, 0)?;
+            terminal.write(text::truncate(&message, (width as usize).saturating_sub(1)))?;
+            // Growing the terminal again is a resize, which redraws everything
+            self.dirty = Dirty::default();
+            return Ok(());
+        }
+        if self.dirty.usage_bar {
+            cursor.goto(0, 0)?;
+            self.usage_bar.draw(&self.state, &self.keymap)?;
+        }
+        if self.dirty.summary {
+            cursor.goto(0, 1)?;
+            self.summary.draw(&self.repositories, &self.theme)?;
+        }
+        if self.dirty.list {
+            cursor.goto(0, layout::LIST_TOP)?;
+            match self.state {
+                AppState::PathList => {
+                    let repositories = self.repositories.repositories_sorted()?;
+                    self.path_list.draw(&repositories, &self.theme)?;
+                },
+                AppState::Details { ref path, global } => {
+                    let repository = self.repositories.find_by_path(path.clone(), global)?;
+                    if let Some(repository) = repository {
+                        self.details.draw(repository, &self.theme)?;
+                    }
                 }
             }
         }
-        self.status_bar.draw(&self.root_path)?;
-        if self.help_window.show {
-            self.help_window.draw()?;
+        if self.dirty.status_bar {
+            cursor.goto(0, height.saturating_sub(1))?;
+            self.status_bar.draw()?;
+        }
+        // The help window covers the list, so it has to be drawn again on top of it
+        if self.help_window.show && self.dirty.list {
+            self.help_window.draw(&self.state)?;
         }
+        self.dirty = Dirty::default();
         Ok(())
     }
 }
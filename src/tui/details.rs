@@ -1,71 +1,167 @@
+use std::collections::HashSet;
 use std::path::PathBuf;
 
-use crossterm::{KeyEvent, InputEvent};
-use number_prefix::{NumberPrefix, Standalone, Prefixed};
+use crossterm::InputEvent;
 
-use crate::repository::Repository;
+use rpcl::Result;
+use rpcl::{Repository, IgnoredPathInfo};
+use crate::format::size_str;
 use crate::tui::list::List;
+use crate::tui::theme::Theme;
+use crate::tui::layout::NARROW_WIDTH;
+use crate::tui::text;
+use crate::tui::pathdisplay::PathDisplay;
+use crate::tui::keymap::{Action, Keymap};
 
 pub struct Details {
     pub list: List,
+    /// Paths whose selection differs from the default, which is to select regenerable paths
+    pub toggled: HashSet<PathBuf>,
+    pub path_display: PathDisplay,
 }
 
 pub enum Event {
     Close,
-    DeleteAll,
+    /// Deletes the selected paths of the repository
+    DeleteSelected(Vec<PathBuf>),
     Delete(PathBuf),
+    /// Removes only the stale parts of a Cargo `target` directory
+    Sweep(PathBuf),
 }
 
-fn size_str(size: u64) -> String {
-    match NumberPrefix::binary(size as f64) {
-        Standalone(bytes) => format!("{:>6}   B", bytes),
-        Prefixed(prefix, n) => format!("{:>6.1} {}B", n, prefix),
+impl Details {
+    pub fn new(list: List, path_display: PathDisplay) -> Self {
+        Self {
+            list,
+            toggled: HashSet::new(),
+            path_display,
+        }
     }
-}
 
-impl Details {
-    pub fn input(&mut self, event: InputEvent, repository: &Repository) -> Result<Option<Event>, Box<dyn std::error::Error>> {
-        self.list.input(event.clone(), 1 + repository.ignored_path_infos().len());
-        match event {
-            InputEvent::Keyboard(k) => {
-                match k {
-                    KeyEvent::Char(c) => {
-                        match c {
-                            'd' => {
-                                if self.list.pos == 0 {
-                                    return Ok(Some(Event::DeleteAll));
-                                } else {
-                                    return Ok(Some(Event::Delete(repository.ignored_path_infos()[self.list.pos - 1].path().to_path_buf())));
-                                }
-                            },
-                            _ => {
-                            },
+    pub fn is_selected(&self, ignored_path_info: &IgnoredPathInfo) -> bool {
+        ignored_path_info.regenerable() != self.toggled.contains(ignored_path_info.path())
+    }
+
+    /// Rows of the repository, its ignored paths and, if any, its warnings.
+    pub fn row_count(repository: &Repository) -> usize {
+        let warnings = if repository.warnings().is_empty() { 0 } else { 1 + repository.warnings().len() };
+        1 + repository.ignored_path_infos().len() + warnings
+    }
+
+    /// The ignored path on the selected row, unless the repository or a warning is selected.
+    fn selected_info<'a>(&self, repository: &'a Repository) -> Option<&'a IgnoredPathInfo> {
+        self.list.selected().checked_sub(1).and_then(|i| repository.ignored_path_infos().get(i))
+    }
+
+    pub fn input(&mut self, event: InputEvent, repository: &Repository, keymap: &Keymap) -> Result<Option<Event>> {
+        // Double clicks only select here; there is nothing to open in the details view.
+        self.list.input(event.clone(), Self::row_count(repository), keymap);
+        if let InputEvent::Keyboard(k) = event {
+            match keymap.find(&k, &[Action::Delete, Action::Sweep, Action::Toggle, Action::Back]) {
+                Some(Action::Delete) => {
+                    if self.list.selected() == 0 {
+                        return Ok(Some(Event::DeleteSelected(repository.ignored_path_infos()
+                                                             .iter()
+                                                             .filter(|i| self.is_selected(i))
+                                                             .map(|i| i.path().to_path_buf())
+                                                             .collect())));
+                    } else if let Some(info) = self.selected_info(repository) {
+                        return Ok(Some(Event::Delete(info.path().to_path_buf())));
+                    }
+                },
+                Some(Action::Sweep) => {
+                    if let Some(info) = self.selected_info(repository) {
+                        if info.is_sweepable() {
+                            return Ok(Some(Event::Sweep(info.path().to_path_buf())));
                         }
-                    },
-                    KeyEvent::Enter => {
-                        self.list.pos = 0;
-                        return Ok(Some(Event::Close));
-                    },
-                    _ => {},
-                }
-            },
-            _ => {},
+                    }
+                },
+                Some(Action::Toggle) => {
+                    if let Some(info) = self.selected_info(repository) {
+                        let path = info.path().to_path_buf();
+                        if !self.toggled.remove(&path) {
+                            self.toggled.insert(path);
+                        }
+                    }
+                },
+                Some(Action::Back) => {
+                    self.list.pos = 0;
+                    self.list.offset = 0;
+                    return Ok(Some(Event::Close));
+                },
+                _ => {},
+            }
         }
         Ok(None)
     }
-     
-    pub fn draw(&self, repository: Repository) -> crossterm::Result<()> {
-        use std::cmp::max;
-        let terminal = crossterm::terminal();
-        let (width, _height) = terminal.size()?;
+
+    pub fn draw(&self, repository: Repository, theme: &Theme) -> crossterm::Result<()> {
+        let (width, _height) = crossterm::terminal().size()?;
+        // Narrow terminals leave out the kinds and the sweep breakdown to make room for the paths
+        let narrow = width < NARROW_WIDTH;
+        // Lines are kept off the last column, where the scroll bar goes
+        let width = (width as usize).saturating_sub(1);
         let mut strings = Vec::new();
-        strings.push(format!("{:<11}{}\r\n", size_str(repository.size()), repository.path().to_string_lossy()));
-        let path_width = width as isize - 15;
+        let warnings_str = match repository.warnings().len() {
+            0 => String::new(),
+            1 => "  (1 warning below)".to_owned(),
+            n => format!("  ({} warnings below)", n),
+        };
+        let (path, parent) = if repository.is_global() {
+            (format!("[{}] {}", repository.root(), self.path_display.show(repository.path(), None).0), None)
+        } else {
+            // The parent is shown too here, since there's only the one path
+            self.path_display.show(repository.path(), Some(repository.root()))
+        };
+        let warnings_str = text::truncate(&warnings_str, width.saturating_sub(11));
+        let path_width = width.saturating_sub(11 + text::width(warnings_str));
+        let mut path_str = text::truncate_middle(&path, path_width);
+        // The parent gets the room the name leaves, less the space and parentheses around it
+        let parent_width = path_width.saturating_sub(text::width(&path_str) + 3);
+        if let Some(parent) = parent.filter(|_| parent_width > 0) {
+            path_str = format!("{} {}", path_str, theme.dim(&format!("({})", text::truncate_middle(&parent, parent_width))));
+        }
+        strings.push(format!("{}{}{}\r\n",
+                             theme.paint(theme.size_color(repository.size()), &format!("{:<11}", size_str(repository.size()))),
+                             path_str,
+                             warnings_str));
         for ignored_path_info in repository.ignored_path_infos().iter() {
-            let mut path_str = ignored_path_info.path().to_string_lossy().to_string();
-            let path_str = path_str.split_off(repository.path().to_string_lossy().len());
-            if ignored_path_info.size() > 0 {
-                strings.push(format!("    {:<11}{}\r\n", size_str(ignored_path_info.size()), path_str));
+            // Global caches may live outside the home directory, e.g. under `$CARGO_HOME`
+            let path_str = match ignored_path_info.path().strip_prefix(repository.path()) {
+                Ok(relative) => format!("/{}", relative.to_string_lossy()),
+                Err(_) => ignored_path_info.path().to_string_lossy().to_string(),
+            };
+            let kind = ignored_path_info.kind();
+            let mark = if self.is_selected(ignored_path_info) { "[x] " } else { "[ ] " };
+            let (kind_str, kind_width) = if narrow {
+                (String::new(), 0)
+            } else {
+                (theme.paint(theme.kind_color(kind), &format!("{:<8}", kind.name())), 8)
+            };
+            let sweep_str = match ignored_path_info.sweep() {
+                Some(report) if report.total() > 0 && !narrow => format!(
+                    "  (sweepable: {} incremental, {} other toolchains, {} outdated)",
+                    size_str(report.incremental).trim(),
+                    size_str(report.other_toolchain).trim(),
+                    size_str(report.outdated).trim()),
+                _ => String::new(),
+            };
+            let path_width = width.saturating_sub(mark.len() + 11 + kind_width);
+            // The path matters more than the sweep breakdown, which goes first when space runs out
+            let sweep_str = if text::width(&path_str) + text::width(&sweep_str) <= path_width { sweep_str } else { String::new() };
+            strings.push(format!("{}{}{}{}{}\r\n",
+                                 mark,
+                                 theme.paint(theme.size_color(ignored_path_info.size()), &format!("{:<11}", size_str(ignored_path_info.size()))),
+                                 kind_str,
+                                 text::truncate_middle(&path_str, path_width),
+                                 sweep_str));
+        }
+        if !repository.warnings().is_empty() {
+            let summary = format!("Skipped while scanning: {}", repository.warning_summary());
+            strings.push(format!("{}\r\n", text::truncate(&summary, width)));
+            for warning in repository.warnings().iter() {
+                let line = format!("  {}: {}", warning.path.to_string_lossy(), warning.message);
+                strings.push(format!("{}\r\n", text::truncate(&line, width)));
             }
         }
         self.list.draw(&strings)?;
@@ -0,0 +1,92 @@
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crossterm::{input, InputEvent};
+
+use rpcl::{Error, Result};
+
+/// How often `Event::Tick` is sent, which sets the speed of scrolling long paths.
+const TICK_INTERVAL: Duration = Duration::from_millis(67);
+
+/// Everything the TUI reacts to, delivered through a single channel.
+pub enum Event {
+    Input(InputEvent),
+    Collector(rpcl::Event),
+    /// Drives the spinner, scrolling of long paths and purging of expired staged paths
+    Tick,
+    /// The result of a clean or sweep running in the background, with the error of logging it
+    Cleaned(Result<Option<Error>>),
+    /// Staged paths whose grace period ran out have been deleted in the background
+    Purged(Result<()>),
+    /// The sweeps of the repository whose details are shown have been planned
+    SweepsPlanned(Result<()>),
+    Signal(Signal),
+    /// The terminal was resized
+    Resize,
+    /// A background thread panicked, with the panic message
+    Panicked(String),
+}
+
+/// What a signal asks the TUI to do.
+pub enum Signal {
+    Quit,
+    Suspend,
+}
+
+pub fn spawn_input(tx: Sender<Event>) {
+    thread::spawn(move || {
+        for event in input().read_sync() {
+            if tx.send(Event::Input(event)).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Turns `Event::Tick` on and off, so that the TUI sleeps while nothing moves.
+pub struct Ticker {
+    running: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl Ticker {
+    pub fn set_running(&self, running: bool) -> Result<()> {
+        let (lock, condvar) = &*self.running;
+        let mut current = lock.lock()?;
+        if *current != running {
+            *current = running;
+            condvar.notify_one();
+        }
+        Ok(())
+    }
+}
+
+pub fn spawn_ticks(tx: Sender<Event>) -> Ticker {
+    let running = Arc::new((Mutex::new(true), Condvar::new()));
+    let ticker = Ticker { running: running.clone() };
+    thread::spawn(move || {
+        let (lock, condvar) = &*running;
+        loop {
+            match lock.lock().and_then(|running| condvar.wait_while(running, |running| !*running)) {
+                Ok(_) => {},
+                Err(_) => break,
+            }
+            thread::sleep(TICK_INTERVAL);
+            if tx.send(Event::Tick).is_err() {
+                break;
+            }
+        }
+    });
+    ticker
+}
+
+pub fn forward_collector(collector_rx: Receiver<rpcl::Event>, tx: Sender<Event>) {
+    thread::spawn(move || {
+        for event in collector_rx {
+            if tx.send(Event::Collector(event)).is_err() {
+                break;
+            }
+        }
+    });
+}
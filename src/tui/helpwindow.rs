@@ -1,30 +1,114 @@
+use std::path::PathBuf;
+
+use crossterm::KeyEvent;
+
+use rpcl::Result;
+use crate::tui::app::AppState;
+use crate::tui::keymap::{key_name, Action, Keymap};
 use crate::tui::Window;
 
 pub struct HelpWindow {
     pub show: bool,
     pub window: Window,
+    keymap: Keymap,
+    /// The config file the key bindings are read from
+    config_path: Option<PathBuf>,
 }
 
-const MESSAGE: &'static [&'static str] = &[
-    "  j,k: Move up, down",
-    "  g,G: Go to top, bottom",
-    "Enter: Show details of the selected repository",
-    "    d: Delete selected resources",
-    "    h: Show this message",
-];
+/// The actions that do something in `state`, in the order they're listed.
+fn actions(state: &AppState) -> &'static [Action] {
+    match state {
+        AppState::PathList => &[
+            Action::Down, Action::Up, Action::Top, Action::Bottom, Action::PageUp, Action::PageDown,
+            Action::HalfPageUp, Action::HalfPageDown, Action::Percent,
+            Action::Open, Action::Undo, Action::Suspend, Action::Help, Action::Quit,
+        ],
+        AppState::Details { .. } => &[
+            Action::Down, Action::Up, Action::Top, Action::Bottom, Action::PageUp, Action::PageDown,
+            Action::HalfPageUp, Action::HalfPageDown, Action::Percent,
+            Action::Toggle, Action::Delete, Action::Sweep, Action::Back, Action::Undo, Action::Suspend, Action::Help, Action::Quit,
+        ],
+    }
+}
+
+/// Lists the keys of the actions of `state`, leaving out actions without keys.
+fn message(state: &AppState, keymap: &Keymap, config_path: Option<&PathBuf>) -> Vec<String> {
+    let actions = actions(state).iter().filter(|action| !keymap.keys(**action).is_empty()).collect::<Vec<_>>();
+    let width = actions.iter().map(|action| keymap.key_names(**action).chars().count()).max().unwrap_or(0);
+    let mut message = vec![format!("rpcl {}", env!("CARGO_PKG_VERSION")), String::new()];
+    message.extend(actions.iter()
+        .map(|action| format!("{:>width$}: {}", keymap.key_names(**action), action.description(), width = width)));
+    message.push(String::new());
+    if let Some(down) = keymap.keys(Action::Down).first() {
+        message.push(format!("A count typed before a move repeats it, e.g. 5{}", key_name(down)));
+    }
+    message.push(match config_path {
+        Some(path) if path.exists() => format!("Key bindings from {}", path.to_string_lossy()),
+        Some(path) => format!("Default key bindings, which {} can change", path.to_string_lossy()),
+        None => "Default key bindings".to_owned(),
+    });
+    message
+}
 
 impl HelpWindow {
-    pub fn new() -> Self {
+    pub fn new(keymap: &Keymap, config_path: Option<PathBuf>) -> Self {
         Self {
             show: false,
-            window: Window {
-                message: MESSAGE.iter().map(|m| m.to_string()).collect::<Vec<_>>(),
-            }
+            window: Window::new(Vec::new()),
+            keymap: keymap.clone(),
+            config_path,
         }
     }
 
-    pub fn draw(&self) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn toggle(&mut self) {
+        self.show = !self.show;
+        self.window.offset = 0;
+    }
+
+    /// Scrolls the help with the movement keys. Returns whether the key was one of them.
+    pub fn input(&mut self, key: &KeyEvent, state: &AppState) -> Result<bool> {
+        self.window.message = message(state, &self.keymap, self.config_path.as_ref());
+        let (_width, height) = crossterm::terminal().size()?;
+        let page = std::cmp::max(height as isize - 1, 1);
+        match self.keymap.find(key, &[Action::Down, Action::Up, Action::PageDown, Action::PageUp,
+                                      Action::HalfPageDown, Action::HalfPageUp, Action::Top, Action::Bottom]) {
+            Some(Action::Down) => self.window.scroll_by(1)?,
+            Some(Action::Up) => self.window.scroll_by(-1)?,
+            Some(Action::PageDown) => self.window.scroll_by(page)?,
+            Some(Action::PageUp) => self.window.scroll_by(-page)?,
+            Some(Action::HalfPageDown) => self.window.scroll_by(page / 2)?,
+            Some(Action::HalfPageUp) => self.window.scroll_by(-page / 2)?,
+            Some(Action::Top) => self.window.offset = 0,
+            Some(Action::Bottom) => self.window.offset = self.window.max_offset()?,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    pub fn draw(&mut self, state: &AppState) -> Result<()> {
+        self.window.message = message(state, &self.keymap, self.config_path.as_ref());
         self.window.draw()?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message() {
+        let keymap = Keymap::new().with_keys(Action::Help, vec![KeyEvent::Char('?')]).with_keys(Action::Sweep, vec![]);
+        let message = message(&AppState::Details { path: PathBuf::new(), global: false }, &keymap, None);
+        assert!(message[0].starts_with("rpcl "));
+        assert!(message.iter().any(|line| line.trim_start() == "j, Down: Move down"));
+        assert!(message.iter().any(|line| line.trim_start() == "?: Show or hide this message"));
+        assert!(message.iter().any(|line| line.trim_start().starts_with("Space: ")));
+        assert!(!message.iter().any(|line| line.contains("Sweep")));
+        assert_eq!(message.last().unwrap(), "Default key bindings");
+
+        let path_list = super::message(&AppState::PathList, &keymap, Some(&PathBuf::from("/nonexistent/config.toml")));
+        assert!(!path_list.iter().any(|line| line.trim_start().starts_with("Space: ")));
+        assert!(path_list.iter().any(|line| line.contains("/nonexistent/config.toml can change")));
+    }
+}
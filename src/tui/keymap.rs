@@ -0,0 +1,338 @@
+use std::collections::BTreeMap;
+
+use crossterm::KeyEvent;
+
+use rpcl::{Error, Result};
+
+/// Everything a key can be bound to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Down,
+    Up,
+    Top,
+    Bottom,
+    PageUp,
+    PageDown,
+    HalfPageUp,
+    HalfPageDown,
+    Percent,
+    Open,
+    Back,
+    Toggle,
+    Delete,
+    Sweep,
+    Undo,
+    Suspend,
+    Help,
+    Quit,
+}
+
+impl Action {
+    pub const ALL: &'static [Action] = &[
+        Action::Down,
+        Action::Up,
+        Action::Top,
+        Action::Bottom,
+        Action::PageUp,
+        Action::PageDown,
+        Action::HalfPageUp,
+        Action::HalfPageDown,
+        Action::Percent,
+        Action::Open,
+        Action::Back,
+        Action::Toggle,
+        Action::Delete,
+        Action::Sweep,
+        Action::Undo,
+        Action::Suspend,
+        Action::Help,
+        Action::Quit,
+    ];
+
+    /// The name used in the config file.
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Down => "down",
+            Action::Up => "up",
+            Action::Top => "top",
+            Action::Bottom => "bottom",
+            Action::PageUp => "page_up",
+            Action::PageDown => "page_down",
+            Action::HalfPageUp => "half_page_up",
+            Action::HalfPageDown => "half_page_down",
+            Action::Percent => "percent",
+            Action::Open => "open",
+            Action::Back => "back",
+            Action::Toggle => "toggle",
+            Action::Delete => "delete",
+            Action::Sweep => "sweep",
+            Action::Undo => "undo",
+            Action::Suspend => "suspend",
+            Action::Help => "help",
+            Action::Quit => "quit",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().cloned().find(|action| action.name() == name)
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::Down => "Move down",
+            Action::Up => "Move up",
+            Action::Top => "Go to the top, or to row N after a count N",
+            Action::Bottom => "Go to the bottom, or to row N after a count N",
+            Action::PageUp => "Move a page up",
+            Action::PageDown => "Move a page down",
+            Action::HalfPageUp => "Move half a page up",
+            Action::HalfPageDown => "Move half a page down",
+            Action::Percent => "Go to N percent of the list after a count N",
+            Action::Open => "Show details of the selected repository (! marks entries that couldn't be scanned)",
+            Action::Back => "Go back to the list of repositories",
+            Action::Toggle => "Select or unselect a resource (regenerable ones are selected by default)",
+            Action::Delete => "Delete the resource. On the repository row, delete only the selected resources, not all of them",
+            Action::Sweep => "Sweep incremental caches and stale artifacts out of a Cargo target",
+            Action::Undo => "Undo the last deletion (with --staging)",
+            Action::Suspend => "Suspend (resume with fg)",
+            Action::Help => "Show or hide this message",
+            Action::Quit => "Quit",
+        }
+    }
+
+    fn default_keys(self) -> Vec<KeyEvent> {
+        match self {
+            Action::Down => vec![KeyEvent::Char('j'), KeyEvent::Down],
+            Action::Up => vec![KeyEvent::Char('k'), KeyEvent::Up],
+            Action::Top => vec![KeyEvent::Char('g'), KeyEvent::Home],
+            Action::Bottom => vec![KeyEvent::Char('G'), KeyEvent::End],
+            Action::PageUp => vec![KeyEvent::Ctrl('b'), KeyEvent::PageUp],
+            Action::PageDown => vec![KeyEvent::Ctrl('f'), KeyEvent::PageDown],
+            Action::HalfPageUp => vec![KeyEvent::Ctrl('u')],
+            Action::HalfPageDown => vec![KeyEvent::Ctrl('d')],
+            Action::Percent => vec![KeyEvent::Char('%')],
+            Action::Open => vec![KeyEvent::Enter],
+            Action::Back => vec![KeyEvent::Enter],
+            Action::Toggle => vec![KeyEvent::Char(' ')],
+            Action::Delete => vec![KeyEvent::Char('d')],
+            Action::Sweep => vec![KeyEvent::Char('s')],
+            Action::Undo => vec![KeyEvent::Char('u')],
+            // Raw mode turns Ctrl-Z into a key press instead of SIGTSTP
+            Action::Suspend => vec![KeyEvent::Ctrl('z')],
+            Action::Help => vec![KeyEvent::Char('h')],
+            Action::Quit => vec![KeyEvent::Char('q'), KeyEvent::Ctrl('c')],
+        }
+    }
+}
+
+/// Actions that can fire on the same key press, as each state handles the actions of every state
+/// along with its own: the movements and opening on the list of repositories, the movements
+/// and actions on resources in the details. The help window takes the movements only.
+const STATES: &[&[Action]] = &[
+    &[
+        Action::Quit, Action::Help, Action::Undo, Action::Suspend,
+        Action::Down, Action::Up, Action::Top, Action::Bottom, Action::PageUp, Action::PageDown,
+        Action::HalfPageUp, Action::HalfPageDown, Action::Percent,
+        Action::Open,
+    ],
+    &[
+        Action::Quit, Action::Help, Action::Undo, Action::Suspend,
+        Action::Down, Action::Up, Action::Top, Action::Bottom, Action::PageUp, Action::PageDown,
+        Action::HalfPageUp, Action::HalfPageDown, Action::Percent,
+        Action::Delete, Action::Sweep, Action::Toggle, Action::Back,
+    ],
+];
+
+/// The keys bound to each action.
+#[derive(Clone)]
+pub struct Keymap {
+    bindings: Vec<(Action, Vec<KeyEvent>)>,
+}
+
+impl Keymap {
+    pub fn new() -> Self {
+        Self {
+            bindings: Action::ALL.iter().map(|action| (*action, action.default_keys())).collect(),
+        }
+    }
+
+    /// Binds `keys` to `action` instead of its current keys.
+    pub fn with_keys(mut self, action: Action, keys: Vec<KeyEvent>) -> Self {
+        for binding in self.bindings.iter_mut().filter(|(a, _)| *a == action) {
+            binding.1 = keys.clone();
+        }
+        self
+    }
+
+    /// The default keymap with the keys of the config file's `[keys]` table.
+    /// Fails on digits, which make counts, and on keys bound to two actions of the same state.
+    pub fn from_config(keys: &BTreeMap<String, Vec<String>>) -> Result<Self> {
+        let mut keymap = Self::new();
+        for (name, key_names) in keys {
+            let action = Action::from_name(name)
+                .ok_or_else(|| Error::Config(format!("unknown action \"{}\"", name)))?;
+            let keys = key_names.iter()
+                .map(|key_name| parse_key(key_name).ok_or_else(|| Error::Config(format!("unknown key \"{}\" for {}", key_name, name))))
+                .collect::<Result<Vec<_>>>()?;
+            keymap = keymap.with_keys(action, keys);
+        }
+        keymap.check()?;
+        Ok(keymap)
+    }
+
+    fn check(&self) -> Result<()> {
+        for (action, keys) in self.bindings.iter() {
+            if let Some(key) = keys.iter().find(|key| matches!(key, KeyEvent::Char(c) if c.is_ascii_digit())) {
+                return Err(Error::Config(format!("{} can't be bound to \"{}\" since digits are counts", action.name(), key_name(key))));
+            }
+        }
+        for actions in STATES.iter() {
+            for (i, action) in actions.iter().enumerate() {
+                for other in actions[i + 1..].iter() {
+                    if let Some(key) = self.keys(*action).iter().find(|key| self.keys(*other).contains(key)) {
+                        return Err(Error::Config(format!("\"{}\" is bound to both {} and {}", key_name(key), action.name(), other.name())));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The first of `actions` that `key` is bound to.
+    pub fn find(&self, key: &KeyEvent, actions: &[Action]) -> Option<Action> {
+        actions.iter().cloned().find(|action| self.is(key, *action))
+    }
+
+    pub fn is(&self, key: &KeyEvent, action: Action) -> bool {
+        self.keys(action).contains(key)
+    }
+
+    pub fn keys(&self, action: Action) -> &[KeyEvent] {
+        self.bindings.iter().find(|(a, _)| *a == action).map(|(_, keys)| keys.as_slice()).unwrap_or(&[])
+    }
+
+    /// The keys of `action` as shown to the user, e.g. `j, Down`.
+    pub fn key_names(&self, action: Action) -> String {
+        self.keys(action).iter().map(key_name).collect::<Vec<_>>().join(", ")
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Parses keys written like `j`, `Space`, `Enter`, `PgDn`, `F1`, `^f`, `Ctrl-f` or `Alt-f`.
+pub fn parse_key(name: &str) -> Option<KeyEvent> {
+    let single = |s: &str| {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None,
+        }
+    };
+    if let Some(c) = single(name) {
+        return Some(KeyEvent::Char(c));
+    }
+    for prefix in &["^", "Ctrl-", "C-"] {
+        if let Some(c) = name.strip_prefix(prefix).and_then(single) {
+            return Some(KeyEvent::Ctrl(c.to_ascii_lowercase()));
+        }
+    }
+    for prefix in &["Alt-", "M-"] {
+        if let Some(c) = name.strip_prefix(prefix).and_then(single) {
+            return Some(KeyEvent::Alt(c));
+        }
+    }
+    if let Some(n) = name.strip_prefix('F').and_then(|n| n.parse().ok()) {
+        return Some(KeyEvent::F(n));
+    }
+    let key = match name {
+        "Space" => KeyEvent::Char(' '),
+        "Enter" => KeyEvent::Enter,
+        "Esc" => KeyEvent::Esc,
+        "Tab" => KeyEvent::Tab,
+        "BackTab" => KeyEvent::BackTab,
+        "Backspace" => KeyEvent::Backspace,
+        "Delete" | "Del" => KeyEvent::Delete,
+        "Insert" => KeyEvent::Insert,
+        "Left" => KeyEvent::Left,
+        "Right" => KeyEvent::Right,
+        "Up" => KeyEvent::Up,
+        "Down" => KeyEvent::Down,
+        "Home" => KeyEvent::Home,
+        "End" => KeyEvent::End,
+        "PageUp" | "PgUp" => KeyEvent::PageUp,
+        "PageDown" | "PgDn" => KeyEvent::PageDown,
+        _ => return None,
+    };
+    Some(key)
+}
+
+/// The short name of a key, which `parse_key` reads back.
+pub fn key_name(key: &KeyEvent) -> String {
+    match key {
+        KeyEvent::Char(' ') => "Space".to_owned(),
+        KeyEvent::Char(c) => c.to_string(),
+        KeyEvent::Ctrl(c) => format!("^{}", c),
+        KeyEvent::Alt(c) => format!("Alt-{}", c),
+        KeyEvent::F(n) => format!("F{}", n),
+        KeyEvent::PageUp => "PgUp".to_owned(),
+        KeyEvent::PageDown => "PgDn".to_owned(),
+        KeyEvent::Delete => "Del".to_owned(),
+        key => format!("{:?}", key),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_key() {
+        for name in &["j", "G", "%", "Space", "Enter", "^f", "Alt-x", "F5", "PgDn", "Del", "Home", "Esc"] {
+            assert_eq!(key_name(&parse_key(name).unwrap()), *name);
+        }
+        assert_eq!(parse_key("Ctrl-F"), Some(KeyEvent::Ctrl('f')));
+        assert_eq!(parse_key("C-f"), Some(KeyEvent::Ctrl('f')));
+        assert_eq!(parse_key("Foo"), None);
+        assert_eq!(parse_key(""), None);
+    }
+
+    #[test]
+    fn test_from_config() {
+        let mut keys = BTreeMap::new();
+        keys.insert("open".to_owned(), vec!["l".to_owned(), "Enter".to_owned()]);
+        keys.insert("help".to_owned(), vec!["?".to_owned()]);
+        let keymap = Keymap::from_config(&keys).unwrap();
+        assert!(keymap.is(&KeyEvent::Char('l'), Action::Open));
+        assert!(keymap.is(&KeyEvent::Char('?'), Action::Help));
+        assert!(!keymap.is(&KeyEvent::Char('h'), Action::Help));
+        assert!(keymap.is(&KeyEvent::Char('j'), Action::Down));
+
+        keys.insert("fly".to_owned(), vec!["f".to_owned()]);
+        assert!(Keymap::from_config(&keys).is_err());
+        keys.remove("fly");
+        keys.insert("quit".to_owned(), vec!["Ctrl-Alt-q".to_owned()]);
+        assert!(Keymap::from_config(&keys).is_err());
+    }
+
+    #[test]
+    fn test_from_config_conflicts() {
+        let config = |bindings: &[(&str, &str)]| {
+            let keys = bindings.iter().map(|(action, key)| (action.to_string(), vec![key.to_string()])).collect();
+            Keymap::from_config(&keys)
+        };
+        assert!(Keymap::new().check().is_ok());
+        // Help is still on h
+        assert!(config(&[("back", "h")]).is_err());
+        assert!(config(&[("back", "h"), ("help", "?")]).is_ok());
+        // The details move with j before deleting
+        assert!(config(&[("delete", "j")]).is_err());
+        // Open and delete never fire in the same state
+        assert!(config(&[("delete", "Enter"), ("back", "Esc")]).is_ok());
+        assert!(config(&[("down", "1")]).is_err());
+        assert!(config(&[("quit", "0")]).is_err());
+    }
+}
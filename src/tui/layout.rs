@@ -0,0 +1,57 @@
+//! Sizes of the screen regions, shared by the widgets so that they agree on the layout.
+
+use crate::tui::text;
+
+/// Below this the columns can't be laid out sensibly, so only a message is shown.
+pub const MIN_WIDTH: u16 = 40;
+/// The usage bar, the summary, one row of the list and the status bar.
+pub const MIN_HEIGHT: u16 = 4;
+
+/// Below this width the optional columns (size bars, artifact kinds) are left out.
+pub const NARROW_WIDTH: u16 = 72;
+
+/// The row the list starts at, below the usage bar and the summary.
+pub const LIST_TOP: u16 = 2;
+
+pub fn is_too_small(width: u16, height: u16) -> bool {
+    width < MIN_WIDTH || height < MIN_HEIGHT
+}
+
+/// Rows left for the list between the summary and the status bar.
+pub fn list_height(height: u16) -> usize {
+    (height as usize).saturating_sub(LIST_TOP as usize + 1)
+}
+
+/// How many of `segments`, joined by `separator`, fit into `width` columns. The first one is always kept.
+pub fn fitting_segments(segments: &[String], separator: &str, width: usize) -> usize {
+    let mut used = 0;
+    for (i, segment) in segments.iter().enumerate() {
+        let needed = if i == 0 { 0 } else { text::width(separator) } + text::width(segment);
+        if i > 0 && used + needed > width {
+            return i;
+        }
+        used += needed;
+    }
+    segments.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_height() {
+        assert_eq!(list_height(20), 17);
+        assert_eq!(list_height(3), 0);
+        assert_eq!(list_height(0), 0);
+    }
+
+    #[test]
+    fn test_fitting_segments() {
+        let segments = vec!["abc".to_string(), "de".to_string(), "f".to_string()];
+        assert_eq!(fitting_segments(&segments, " | ", 100), 3);
+        assert_eq!(fitting_segments(&segments, " | ", 8), 2);
+        assert_eq!(fitting_segments(&segments, " | ", 7), 1);
+        assert_eq!(fitting_segments(&segments, " | ", 1), 1);
+    }
+}
@@ -1,30 +1,87 @@
-use crossterm::{InputEvent, KeyEvent, ClearType, Attribute};
-use std::cmp::min;
+use crossterm::{InputEvent, KeyEvent, MouseEvent, MouseButton, ClearType, Attribute};
+use std::cmp::{min, max};
+use std::time::{Duration, Instant};
+
+use crate::tui::keymap::{Action, Keymap};
+
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The actions `List::input` handles.
+pub const MOVES: &[Action] = &[
+    Action::Down,
+    Action::Up,
+    Action::Top,
+    Action::Bottom,
+    Action::Percent,
+    Action::PageUp,
+    Action::PageDown,
+    Action::HalfPageUp,
+    Action::HalfPageDown,
+];
 
 pub struct List {
     pub pos: usize,
     pub offset: usize,
     pub height: usize,
+    /// The screen row the first item is drawn at.
+    pub top: u16,
+    /// Count typed before a movement key, as in `10j`.
+    count: Option<usize>,
+    last_click: Option<(usize, Instant)>,
 }
 
 impl List {
-    pub fn go_up(&mut self) {
-        if self.pos > 0 {
-            self.pos -= 1;
-        } else if self.offset > 0 {
-            self.offset -= 1;
+    pub fn new(top: u16, height: usize) -> Self {
+        Self {
+            pos: 0,
+            offset: 0,
+            height,
+            top,
+            count: None,
+            last_click: None,
         }
     }
 
-    pub fn go_down(&mut self, list_len: usize) {
-        if self.pos + 1 < min(self.height, list_len) {
-            self.pos += 1;
-        } else if self.offset + self.pos + 1 < list_len {
-            self.offset += 1;
+    /// What decides the rows shown: the selected row and the scroll offset.
+    pub fn view(&self) -> (usize, usize) {
+        (self.pos, self.offset)
+    }
+
+    /// The index of the selected item.
+    pub fn selected(&self) -> usize {
+        self.offset + self.pos
+    }
+
+    /// Selects the item at `index`, scrolling as little as possible to keep it visible.
+    pub fn select(&mut self, index: usize, list_len: usize) {
+        if list_len == 0 {
+            self.go_to_top();
+            return;
         }
-        if self.pos >= list_len {
-            self.pos = list_len - 1;
+        let height = max(self.height, 1);
+        let index = min(index, list_len - 1);
+        if index < self.offset {
+            self.offset = index;
+        } else if index >= self.offset + height {
+            self.offset = index + 1 - height;
         }
+        self.pos = index - self.offset;
+    }
+
+    pub fn go_up(&mut self) {
+        self.go_up_by(1);
+    }
+
+    pub fn go_down(&mut self, list_len: usize) {
+        self.go_down_by(1, list_len);
+    }
+
+    pub fn go_up_by(&mut self, n: usize) {
+        self.select(self.selected().saturating_sub(n), usize::MAX);
+    }
+
+    pub fn go_down_by(&mut self, n: usize, list_len: usize) {
+        self.select(self.selected().saturating_add(n), list_len);
     }
 
     pub fn go_to_top(&mut self) {
@@ -33,15 +90,51 @@ impl List {
     }
 
     pub fn go_to_bottom(&mut self, list_len: usize) {
-        if list_len < self.height {
-            self.pos = list_len - 1;
+        self.select(list_len.saturating_sub(1), list_len);
+    }
+
+    /// Goes to the item `percent`% of the way down the list.
+    pub fn go_to_percentage(&mut self, percent: usize, list_len: usize) {
+        let percent = min(percent, 100);
+        self.select((list_len * percent).div_ceil(100).saturating_sub(1), list_len);
+    }
+
+    fn page(&self) -> usize {
+        max(self.height, 1)
+    }
+
+    fn half_page(&self) -> usize {
+        max(self.height / 2, 1)
+    }
+
+    /// Returns the index of the item drawn at the given (1-based) mouse row.
+    fn index_at(&self, mouse_y: u16, list_len: usize) -> Option<usize> {
+        let y = mouse_y.checked_sub(1)?;
+        let row = y.checked_sub(self.top)? as usize;
+        if row < self.height && self.offset + row < list_len {
+            Some(self.offset + row)
         } else {
-            self.pos = self.height - 1;
-            self.offset = list_len - self.height;
+            None
         }
     }
 
-    pub fn draw(&self, strs: &Vec<String>) -> crossterm::Result<()> {
+    /// Selects the clicked row and returns `true` if it was a double click.
+    fn click(&mut self, mouse_y: u16, list_len: usize) -> bool {
+        let index = match self.index_at(mouse_y, list_len) {
+            Some(index) => index,
+            None => return false,
+        };
+        self.select(index, list_len);
+        let now = Instant::now();
+        let double = match self.last_click {
+            Some((last, at)) => last == index && now.duration_since(at) < DOUBLE_CLICK_INTERVAL,
+            None => false,
+        };
+        self.last_click = if double { None } else { Some((index, now)) };
+        double
+    }
+
+    pub fn draw(&self, strs: &[String]) -> crossterm::Result<()> {
         let terminal = crossterm::terminal();
         for i in 0..self.height {
             if let Some(string) = strs.get(self.offset + i) {
@@ -58,38 +151,166 @@ impl List {
                 terminal.write("\r\n")?;
             }
         }
+        self.draw_scrollbar(strs.len())?;
         Ok(())
     }
 
-    pub fn input(&mut self, event: InputEvent, list_len: usize) {
+    /// Draws a scrollbar on the rightmost column if the list doesn't fit.
+    fn draw_scrollbar(&self, list_len: usize) -> crossterm::Result<()> {
+        let (thumb_start, thumb_end) = match scrollbar_thumb(self.offset, self.height, list_len) {
+            Some(thumb) => thumb,
+            None => return Ok(()),
+        };
+        let terminal = crossterm::terminal();
+        let cursor = crossterm::cursor();
+        let (width, _height) = terminal.size()?;
+        for i in 0..self.height {
+            cursor.goto(width.saturating_sub(1), self.top + i as u16)?;
+            terminal.write(if i >= thumb_start && i < thumb_end { "█" } else { "│" })?;
+        }
+        cursor.goto(0, self.top + self.height as u16)?;
+        Ok(())
+    }
+
+    /// Handles movement keys and mouse events.
+    /// Returns `true` if the selected item was activated by a double click.
+    pub fn input(&mut self, event: InputEvent, list_len: usize, keymap: &Keymap) -> bool {
+        let count = self.count.take();
+        let n = count.unwrap_or(1);
         match event {
             InputEvent::Keyboard(k) => {
-                match k {
-                    KeyEvent::Char(c) => match c {
-                        'j' => {
-                            self.go_down(list_len);
-                        },
-                        'k' => {
-                            self.go_up();
-                        },
-                        'g' => {
-                            self.go_to_top();
-                        },
-                        'G' => {
-                            self.go_to_bottom(list_len);
-                        },
-                        _ => {},
+                if let KeyEvent::Char(c @ '0'..='9') = k {
+                    if count.is_some() || c != '0' {
+                        let digit = c.to_digit(10).unwrap() as usize;
+                        self.count = Some(count.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+                        return false;
+                    }
+                }
+                match keymap.find(&k, MOVES) {
+                    Some(Action::Down) => {
+                        self.go_down_by(n, list_len);
+                    },
+                    Some(Action::Up) => {
+                        self.go_up_by(n);
+                    },
+                    Some(Action::Top) => {
+                        self.select(n - 1, list_len);
+                    },
+                    Some(Action::Bottom) => {
+                        match count {
+                            Some(n) => self.select(n.saturating_sub(1), list_len),
+                            None => self.go_to_bottom(list_len),
+                        }
+                    },
+                    Some(Action::Percent) => {
+                        if let Some(n) = count {
+                            self.go_to_percentage(n, list_len);
+                        }
                     },
-                    KeyEvent::Up => {
+                    Some(Action::PageUp) => {
+                        self.go_up_by(n.saturating_mul(self.page()));
+                    },
+                    Some(Action::PageDown) => {
+                        self.go_down_by(n.saturating_mul(self.page()), list_len);
+                    },
+                    Some(Action::HalfPageUp) => {
+                        self.go_up_by(n.saturating_mul(self.half_page()));
+                    },
+                    Some(Action::HalfPageDown) => {
+                        self.go_down_by(n.saturating_mul(self.half_page()), list_len);
+                    },
+                    _ => {},
+                }
+            },
+            InputEvent::Mouse(m) => {
+                match m {
+                    MouseEvent::Press(MouseButton::WheelUp, _, _) => {
                         self.go_up();
                     },
-                    KeyEvent::Down => {
+                    MouseEvent::Press(MouseButton::WheelDown, _, _) => {
                         self.go_down(list_len);
-                    }
+                    },
+                    MouseEvent::Press(MouseButton::Left, _, y) => {
+                        return self.click(y, list_len);
+                    },
                     _ => {},
                 }
-            }
+            },
             _ => {},
         }
+        false
+    }
+}
+
+/// Returns the rows `[start, end)` the scrollbar thumb covers, or `None` if everything fits.
+fn scrollbar_thumb(offset: usize, height: usize, list_len: usize) -> Option<(usize, usize)> {
+    if height == 0 || list_len <= height {
+        return None;
+    }
+    let size = max(height * height / list_len, 1);
+    let start = min(offset * height / list_len, height - size);
+    let start = if offset + height >= list_len { height - size } else { start };
+    Some((start, start + size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_click() {
+        let mut list = List::new(1, 3);
+        assert!(!list.click(1, 10));
+        assert_eq!(list.pos, 0);
+        assert!(!list.click(3, 10));
+        assert_eq!(list.pos, 1);
+        assert!(list.click(3, 10));
+        assert!(!list.click(5, 10));
+        assert_eq!(list.pos, 1);
+        list.offset = 8;
+        assert!(!list.click(4, 10));
+        assert_eq!(list.pos, 1);
+        assert!(!list.click(3, 10));
+        assert_eq!(list.pos, 1);
+    }
+
+    fn press(list: &mut List, keys: &str, list_len: usize) {
+        for c in keys.chars() {
+            list.input(InputEvent::Keyboard(KeyEvent::Char(c)), list_len, &Keymap::new());
+        }
+    }
+
+    #[test]
+    fn test_movement() {
+        let mut list = List::new(1, 10);
+        press(&mut list, "12j", 100);
+        assert_eq!((list.offset, list.pos), (3, 9));
+        press(&mut list, "2k", 100);
+        assert_eq!((list.offset, list.pos), (3, 7));
+        list.input(InputEvent::Keyboard(KeyEvent::Ctrl('d')), 100, &Keymap::new());
+        assert_eq!(list.selected(), 15);
+        list.input(InputEvent::Keyboard(KeyEvent::PageDown), 100, &Keymap::new());
+        assert_eq!(list.selected(), 25);
+        press(&mut list, "50%", 100);
+        assert_eq!(list.selected(), 49);
+        press(&mut list, "G", 100);
+        assert_eq!((list.offset, list.pos), (90, 9));
+        press(&mut list, "0j", 100);
+        assert_eq!(list.selected(), 99);
+        list.input(InputEvent::Keyboard(KeyEvent::Home), 100, &Keymap::new());
+        assert_eq!(list.selected(), 0);
+        press(&mut list, "5G", 3);
+        assert_eq!(list.selected(), 2);
+        press(&mut list, "j", 0);
+        assert_eq!(list.selected(), 0);
+    }
+
+    #[test]
+    fn test_scrollbar_thumb() {
+        assert_eq!(scrollbar_thumb(0, 10, 5), None);
+        assert_eq!(scrollbar_thumb(0, 10, 100), Some((0, 1)));
+        assert_eq!(scrollbar_thumb(90, 10, 100), Some((9, 10)));
+        assert_eq!(scrollbar_thumb(0, 10, 20), Some((0, 5)));
+        assert_eq!(scrollbar_thumb(5, 10, 20), Some((2, 7)));
     }
 }
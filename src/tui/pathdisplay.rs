@@ -0,0 +1,112 @@
+use std::path::{Component, Path, PathBuf};
+
+pub const PATH_DISPLAY_NAMES: &[&str] = &["full", "home", "root", "name"];
+
+/// How repository paths are written in the TUI.
+#[derive(Clone, Copy, PartialEq)]
+pub enum PathDisplayMode {
+    /// As found while scanning, e.g. `./src/project` or `/home/me/src/project`
+    Full,
+    /// Absolute, with the home directory abbreviated to `~`
+    Home,
+    /// Relative to the root directory it was found under
+    Root,
+    /// Only the name of the repository, followed by its parent
+    Name,
+}
+
+impl PathDisplayMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "full" => Some(PathDisplayMode::Full),
+            "home" => Some(PathDisplayMode::Home),
+            "root" => Some(PathDisplayMode::Root),
+            "name" => Some(PathDisplayMode::Name),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct PathDisplay {
+    mode: PathDisplayMode,
+    home: Option<PathBuf>,
+    current_dir: Option<PathBuf>,
+}
+
+impl PathDisplay {
+    pub fn new(mode: PathDisplayMode) -> Self {
+        Self {
+            mode,
+            home: std::env::var_os("HOME").map(PathBuf::from).filter(|home| home.is_absolute()),
+            current_dir: std::env::current_dir().ok(),
+        }
+    }
+
+    /// The path to show for a repository found under `root`, and the parent directory to show after it
+    /// in dim text, if any. `root` is `None` for repositories that aren't under a root, like the global caches.
+    pub fn show(&self, path: &Path, root: Option<&str>) -> (String, Option<String>) {
+        match self.mode {
+            PathDisplayMode::Full => (path.to_string_lossy().to_string(), None),
+            PathDisplayMode::Home => (self.abbreviate_home(path), None),
+            PathDisplayMode::Root => {
+                match root.and_then(|root| path.strip_prefix(root).ok()) {
+                    Some(relative) if relative.as_os_str().is_empty() => (".".to_owned(), None),
+                    Some(relative) => (relative.to_string_lossy().to_string(), None),
+                    None => (self.abbreviate_home(path), None),
+                }
+            },
+            PathDisplayMode::Name => {
+                let path = self.absolute(path);
+                match (path.file_name(), path.parent()) {
+                    (Some(name), Some(parent)) => (name.to_string_lossy().to_string(), Some(self.abbreviate_home(parent))),
+                    _ => (self.abbreviate_home(&path), None),
+                }
+            },
+        }
+    }
+
+    /// Resolves `path` against the current directory, leaving out `.` components.
+    fn absolute(&self, path: &Path) -> PathBuf {
+        let path = match &self.current_dir {
+            Some(current_dir) if path.is_relative() => current_dir.join(path),
+            _ => path.to_path_buf(),
+        };
+        path.components().filter(|c| *c != Component::CurDir).collect()
+    }
+
+    fn abbreviate_home(&self, path: &Path) -> String {
+        let path = self.absolute(path);
+        match self.home.as_ref().and_then(|home| path.strip_prefix(home).ok()) {
+            Some(relative) if relative.as_os_str().is_empty() => "~".to_owned(),
+            Some(relative) => format!("~/{}", relative.to_string_lossy()),
+            None => path.to_string_lossy().to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn display(mode: PathDisplayMode) -> PathDisplay {
+        PathDisplay {
+            mode,
+            home: Some(PathBuf::from("/home/me")),
+            current_dir: Some(PathBuf::from("/home/me/src")),
+        }
+    }
+
+    #[test]
+    fn test_show() {
+        let path = Path::new("./work/project");
+        assert_eq!(display(PathDisplayMode::Full).show(path, Some(".")), ("./work/project".to_owned(), None));
+        assert_eq!(display(PathDisplayMode::Home).show(path, Some(".")), ("~/src/work/project".to_owned(), None));
+        assert_eq!(display(PathDisplayMode::Root).show(path, Some(".")), ("work/project".to_owned(), None));
+        assert_eq!(display(PathDisplayMode::Root).show(Path::new("."), Some(".")), (".".to_owned(), None));
+        assert_eq!(display(PathDisplayMode::Name).show(path, Some(".")), ("project".to_owned(), Some("~/src/work".to_owned())));
+        // The global caches aren't under any root
+        assert_eq!(display(PathDisplayMode::Root).show(Path::new("/home/me"), None), ("~".to_owned(), None));
+        assert_eq!(display(PathDisplayMode::Home).show(Path::new("/opt/project"), Some("/opt")), ("/opt/project".to_owned(), None));
+    }
+}
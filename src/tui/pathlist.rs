@@ -1,13 +1,53 @@
-use crossterm::{InputEvent, KeyEvent, ClearType, Attribute};
-use number_prefix::{NumberPrefix, Standalone, Prefixed};
+use crossterm::InputEvent;
 
+use rpcl::Result;
 use crate::tui::list::List;
-use crate::repository::{Repository, RepositoryStore};
+use crate::format::size_str;
+use crate::tui::theme::{Theme, size_bar};
+use crate::tui::layout::NARROW_WIDTH;
+use crate::tui::text;
+use crate::tui::pathdisplay::PathDisplay;
+use crate::tui::keymap::{Action, Keymap};
+use rpcl::{Repository, RepositoryStore};
 
+const BAR_WIDTH: usize = 10;
+
+/// Parents are only shown after repository names with at least this many columns for them.
+const MIN_PARENT_WIDTH: usize = 8;
+
+/// Scrolling of a long path stops after this many ticks.
+const MAX_PATH_SCROLL: usize = 1000;
+
+pub const OVERFLOW_NAMES: &[&str] = &["scroll", "ellipsis"];
+
+/// How paths too long for the terminal are fitted into it.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Overflow {
+    /// Cuts off the ends, but scrolls through the selected path
+    Scroll,
+    /// Replaces the middles with an ellipsis
+    Ellipsis,
+}
+
+impl Overflow {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "scroll" => Some(Overflow::Scroll),
+            "ellipsis" => Some(Overflow::Ellipsis),
+            _ => None,
+        }
+    }
+}
 
 pub struct PathList {
     pub list: List,
     pub path_scroll_amount: usize,
+    /// How far the path of the selected repository can scroll, as of the last draw
+    pub path_scroll_len: usize,
+    /// Tags each repository with its root directory when searching under several roots
+    pub show_root: bool,
+    pub overflow: Overflow,
+    pub path_display: PathDisplay,
 }
 
 pub enum Event {
@@ -15,102 +55,110 @@ pub enum Event {
 }
 
 impl PathList {
-    pub fn draw(&self, repositories: &Vec<Repository>) -> crossterm::Result<()> {
-        self.list.draw(&repositories
-                       .iter()
-                       .filter(|r| r.size() != 0)
-                       .enumerate()
-                       .flat_map(|(i, r)| self.render_repository(r, i == self.list.offset + self.list.pos))
-                       .collect::<Vec<_>>())?;
+    pub fn draw(&mut self, repositories: &[Repository], theme: &Theme) -> crossterm::Result<()> {
+        let max_size = repositories.iter().map(|r| r.size()).max().unwrap_or(0);
+        let root_width = if self.show_root {
+            repositories.iter().map(|r| text::width(r.root()) + 3).max().unwrap_or(0)
+        } else {
+            0
+        };
+        let mut path_scroll_len = 0;
+        let rows = repositories
+            .iter()
+            .filter(|r| r.size() != 0)
+            .enumerate()
+            .flat_map(|(i, r)| {
+                let selected = i == self.list.offset + self.list.pos;
+                self.render_repository(r, max_size, root_width, theme, selected).map(|(row, scroll_len)| {
+                    if selected {
+                        path_scroll_len = scroll_len;
+                    }
+                    row
+                })
+            })
+            .collect::<Vec<_>>();
+        self.path_scroll_len = path_scroll_len;
+        self.list.draw(&rows)?;
         Ok(())
     }
 
-    fn render_repository(&self, repository: &Repository, selected: bool) -> Result<String, Box<dyn std::error::Error>> {
+    /// Renders a row, along with how far its path can scroll.
+    fn render_repository(&self, repository: &Repository, max_size: u64, root_width: usize, theme: &Theme, selected: bool) -> Result<(String, usize)> {
         let terminal = crossterm::terminal();
         let size = repository.size();
         let (width, _height) = terminal.size()?;
-        let size_str = match NumberPrefix::binary(size as f64) {
-            Standalone(bytes) => format!("{:>6}   B", bytes),
-            Prefixed(prefix, n) => format!("{:>6.1} {}B", n, prefix),
+        let size_str = theme.paint(theme.size_color(size), &format!("{:<11}", size_str(size)));
+        let bar_width = if width < NARROW_WIDTH { 0 } else { BAR_WIDTH + 1 };
+        let bar_str = if bar_width == 0 {
+            String::new()
+        } else {
+            format!("{} ", theme.paint(theme.bar, &size_bar(size, max_size, BAR_WIDTH)))
         };
-        Ok(if selected {
-            let path_str = scroll_line_if_needed(repository.path().to_string_lossy().to_string(), width as usize - 11, self.path_scroll_amount);
-            format!("{:<11}{}\r\n", size_str, path_str)
+        // The global caches aren't under any root, so they're always tagged
+        let root_str = if self.show_root || repository.is_global() {
+            text::pad(&format!("[{}]", repository.root()), root_width)
         } else {
-            let path_str = scroll_line_if_needed(repository.path().to_string_lossy().to_string(), width as usize - 11, 0);
-            format!("{:<11}{}\r\n", size_str, path_str)
-        })
+            String::new()
+        };
+        // Marks repositories with entries that couldn't be scanned, listed in the details
+        let warning_str = if repository.warnings().is_empty() { "" } else { "! " };
+        // Rows are kept off the last column, where the scroll bar goes
+        let path_width = (width as usize).saturating_sub(1 + 11 + bar_width + text::width(&root_str) + warning_str.len());
+        let root = if repository.is_global() { None } else { Some(repository.root()) };
+        let (path, parent) = self.path_display.show(repository.path(), root);
+        let scroll_len = text::scroll_len(&path, path_width);
+        let mut path_str = match self.overflow {
+            Overflow::Scroll => {
+                let path_scroll_amount = if selected { self.path_scroll_amount } else { 0 };
+                text::scroll(&path, path_width, path_scroll_amount).to_owned()
+            },
+            Overflow::Ellipsis => text::truncate_middle(&path, path_width),
+        };
+        // The parent only gets the room the name leaves, and is left out if that's too little to be useful
+        let parent_width = path_width.saturating_sub(text::width(&path_str) + 1);
+        if let Some(parent) = parent.filter(|_| parent_width >= MIN_PARENT_WIDTH) {
+            path_str = format!("{} {}", path_str, theme.dim(&text::truncate_middle(&parent, parent_width)));
+        }
+        Ok((format!("{}{}{}{}{}\r\n", size_str, bar_str, root_str, warning_str, path_str), scroll_len))
     }
 
-    fn get_selected_repository(&self, repositories: &RepositoryStore) -> Result<Repository, Box<dyn std::error::Error>> {
-        let repositories = repositories.repositories_sorted()?;
-        return Ok(repositories[self.list.pos + self.list.offset].clone());
+    /// Whether the path of the selected repository is still scrolling.
+    pub fn is_scrolling(&self) -> bool {
+        self.overflow == Overflow::Scroll && self.path_scroll_amount < std::cmp::min(self.path_scroll_len, MAX_PATH_SCROLL)
     }
 
-    pub fn input(&mut self, event: InputEvent, repositories: &RepositoryStore) -> Result<Option<Event>, Box<dyn std::error::Error>> {
-        self.list.input(event.clone(), repositories.filtered_len()?);
-        match event {
-            InputEvent::Keyboard(k) => {
-                match k {
-                    KeyEvent::Char(c) => match c {
-                        'j' => {
-                            self.path_scroll_amount = 0;
-                        },
-                        'k' => {
-                            self.path_scroll_amount = 0;
-                        },
-                        'g' => {
-                            self.path_scroll_amount = 0;
-                        },
-                        'G' => {
-                            self.path_scroll_amount = 0;
-                        },
-                        _ => {},
-                    },
-                    KeyEvent::Enter => {
-                        return Ok(Some(Event::Open(self.get_selected_repository(repositories)?)));
-                    },
-                    KeyEvent::Up => {
-                        self.path_scroll_amount = 0;
-                    },
-                    KeyEvent::Down => {
-                        self.path_scroll_amount = 0;
-                    }
-                    _ => {},
-                }
-            }
-            _ => {},
+    /// Scrolls the path of the selected repository a bit further.
+    /// Returns whether that changed what's shown, i.e. the path is too long to fit.
+    pub fn tick(&mut self) -> bool {
+        if !self.is_scrolling() {
+            return false;
         }
-        Ok(None)
+        self.path_scroll_amount += 1;
+        true
     }
-}
 
-fn scroll_line_if_needed(mut line: String, width: usize, path_scroll_amount: usize) -> String {
-    if line.len() < width {
-        return line;
-    }
-    if (line.len() as isize) - (path_scroll_amount as isize) < width as isize {
-        return line.split_off(line.len() - width);
+    fn get_selected_repository(&self, repositories: &RepositoryStore) -> Result<Option<Repository>> {
+        // The list leaves out repositories with nothing left to clean
+        Ok(repositories.repositories_sorted()?
+            .into_iter()
+            .filter(|r| r.size() != 0)
+            .nth(self.list.selected()))
     }
-    let mut line = line.split_off(path_scroll_amount);
-    line.split_off(width);
-    line
-}
 
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_scroll_line_if_needed() {
-        assert_eq!(scroll_line_if_needed("".to_string(), 3, 0), "");
-        assert_eq!(scroll_line_if_needed("".to_string(), 3, 2), "");
-        assert_eq!(scroll_line_if_needed("abcde".to_string(), 3, 100), "cde");
-        assert_eq!(scroll_line_if_needed("abc".to_string(), 3, 1), "abc");
-        assert_eq!(scroll_line_if_needed("abcde".to_string(), 3, 0), "abc".to_string());
-        assert_eq!(scroll_line_if_needed("abcde".to_string(), 3, 1), "bcd".to_string());
-        assert_eq!(scroll_line_if_needed("abcde".to_string(), 3, 2), "cde".to_string());
-        assert_eq!(scroll_line_if_needed("abcde".to_string(), 3, 3), "cde".to_string());
+    pub fn input(&mut self, event: InputEvent, repositories: &RepositoryStore, keymap: &Keymap) -> Result<Option<Event>> {
+        let selected = self.list.selected();
+        let activated = self.list.input(event.clone(), repositories.filtered_len()?, keymap);
+        if self.list.selected() != selected {
+            self.path_scroll_amount = 0;
+        }
+        if activated {
+            return Ok(self.get_selected_repository(repositories)?.map(Event::Open));
+        }
+        if let InputEvent::Keyboard(k) = event {
+            if keymap.is(&k, Action::Open) {
+                return Ok(self.get_selected_repository(repositories)?.map(Event::Open));
+            }
+        }
+        Ok(None)
     }
 }
@@ -1,21 +1,131 @@
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
 use crossterm::ClearType;
 
-const SPINNER_STRS: &'static [&'static str] = &["◡◡", "⊙⊙", "◠◠", "⊙⊙"];
+use rpcl::Progress;
+use rpcl::Error;
+use crate::format::size_str;
+use crate::tui::text;
+
+const SPINNER_STRS: &[&str] = &["◡◡", "⊙⊙", "◠◠", "⊙⊙"];
+
+/// How long the collector may stay silent before we point out it might be stuck.
+const STALL_THRESHOLD: Duration = Duration::from_secs(3);
+
+/// How long an error stays on the status bar.
+const ERROR_DURATION: Duration = Duration::from_secs(5);
+
+/// Ticks per frame of the spinner.
+const SPINNER_TICKS: usize = 5;
 
 pub struct StatusBar {
-    pub done: bool,
     pub spinner_phase: usize,
+    ticks: usize,
+    /// The latest progress of each root directory and whether its scan is done
+    pub roots: Vec<(Progress, bool)>,
+    /// The directory most recently reported as being walked
+    pub current: PathBuf,
+    pub last_progress_at: Instant,
+    /// The latest recoverable error or warning, with its label, and when it happened
+    pub error: Option<(String, Instant)>,
 }
 
 impl StatusBar {
-    pub fn draw(&self, root_path: &String) -> crossterm::Result<()> {
+    pub fn new(root_paths: &[String]) -> Self {
+        Self {
+            spinner_phase: 0,
+            ticks: 0,
+            roots: root_paths.iter().map(|root| (Progress { root: root.clone(), ..Progress::default() }, false)).collect(),
+            current: PathBuf::new(),
+            last_progress_at: Instant::now(),
+            error: None,
+        }
+    }
+
+    pub fn show_error(&mut self, error: &Error) {
+        self.error = Some((format!("Error: {}", error), Instant::now()));
+    }
+
+    /// Shows something that went wrong without stopping what was being done.
+    pub fn show_warning(&mut self, warning: &str) {
+        self.error = Some((format!("Warning: {}", warning), Instant::now()));
+    }
+
+    /// Advances the spinner and expires the error. Returns whether the status bar needs drawing again.
+    pub fn tick(&mut self) -> bool {
+        let mut dirty = false;
+        self.ticks += 1;
+        if self.error.as_ref().map(|(_, at)| at.elapsed() >= ERROR_DURATION).unwrap_or(false) {
+            self.error = None;
+            dirty = true;
+        }
+        if !self.done() && self.ticks.is_multiple_of(SPINNER_TICKS) {
+            self.spinner_phase = (self.spinner_phase + 1) % SPINNER_STRS.len();
+            dirty = true;
+        }
+        dirty
+    }
+
+    pub fn update(&mut self, progress: Progress, done: bool) {
+        self.current = progress.current.clone();
+        self.last_progress_at = Instant::now();
+        if let Some(root) = self.roots.iter_mut().find(|(p, _)| p.root == progress.root) {
+            *root = (progress, done);
+        }
+    }
+
+    pub fn done(&self) -> bool {
+        self.roots.iter().all(|(_, done)| *done)
+    }
+
+    /// The progress summed over all root directories.
+    pub fn total(&self) -> Progress {
+        self.roots.iter().fold(Progress::default(), |acc, (p, _)| Progress {
+            directories: acc.directories + p.directories,
+            repositories: acc.repositories + p.repositories,
+            bytes: acc.bytes + p.bytes,
+            skipped: acc.skipped + p.skipped,
+            ..acc
+        })
+    }
+
+    pub fn draw(&self) -> crossterm::Result<()> {
         let terminal = crossterm::terminal();
-        terminal.clear(ClearType::CurrentLine)?;
-        if self.done {
-            terminal.write(format!("Done."))?;
-        } else {
-            terminal.write(format!("{} Searching under {}", SPINNER_STRS[self.spinner_phase], root_path))?;
+        let (width, _height) = terminal.size()?;
+        let total = self.total();
+        let mut counts = format!("{} dirs, {} repos, {} sized",
+                                 total.directories,
+                                 total.repositories,
+                                 size_str(total.bytes).trim());
+        if total.skipped > 0 {
+            counts.push_str(&format!(", {} unreadable dirs skipped", total.skipped));
         }
+        let line = if let Some((error, _)) = self.error.as_ref().filter(|(_, at)| at.elapsed() < ERROR_DURATION) {
+            error.clone()
+        } else if self.done() {
+            format!("Done. {}", counts)
+        } else if self.current.as_os_str().is_empty() {
+            let roots = self.roots.iter().map(|(p, _)| p.root.as_str()).collect::<Vec<_>>();
+            format!("{} Searching under {}", SPINNER_STRS[self.spinner_phase], roots.join(", "))
+        } else {
+            let stalled = self.last_progress_at.elapsed();
+            let stalled = if stalled >= STALL_THRESHOLD {
+                format!(" (no progress for {}s)", stalled.as_secs())
+            } else {
+                String::new()
+            };
+            let roots_done = self.roots.iter().filter(|(_, done)| *done).count();
+            let roots = if self.roots.len() > 1 {
+                format!(" ({}/{} roots done)", roots_done, self.roots.len())
+            } else {
+                String::new()
+            };
+            format!("{} {}{}{} | {}", SPINNER_STRS[self.spinner_phase], counts, roots, stalled, self.current.to_string_lossy())
+        };
+        terminal.clear(ClearType::CurrentLine)?;
+        // Writing to the last column would scroll the screen
+        terminal.write(text::truncate(&line, (width as usize).saturating_sub(1)))?;
         Ok(())
     }
 }
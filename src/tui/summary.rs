@@ -0,0 +1,72 @@
+use std::time::{Duration, Instant};
+
+use crossterm::ClearType;
+
+use rpcl::Result;
+use rpcl::RepositoryStore;
+use crate::format::size_str;
+use crate::tui::theme::Theme;
+use crate::tui::layout::fitting_segments;
+
+const SEPARATOR: &str = " | ";
+
+/// Totals across all repositories and statistics of the scan.
+pub struct Summary {
+    pub started_at: Instant,
+    /// How long the scan took, once it's done
+    pub elapsed: Option<Duration>,
+    pub directories: usize,
+}
+
+impl Summary {
+    pub fn new() -> Self {
+        Self {
+            started_at: Instant::now(),
+            elapsed: None,
+            directories: 0,
+        }
+    }
+
+    pub fn finish(&mut self) {
+        self.elapsed = Some(self.started_at.elapsed());
+    }
+
+    pub fn draw(&self, repositories: &RepositoryStore, theme: &Theme) -> Result<()> {
+        let terminal = crossterm::terminal();
+        let total_size = repositories.total_size()?;
+        let elapsed = self.elapsed.unwrap_or_else(|| self.started_at.elapsed());
+        let staged = match repositories.staging() {
+            Some(staging) => format!(" ({} undoable)", staging.len()?),
+            None => String::new(),
+        };
+        let (width, _height) = terminal.size()?;
+        let total_size_str = size_str(total_size).trim().to_owned();
+        let segments = vec![
+            format!("Reclaimable: {} in {}/{} repos", total_size_str, repositories.filtered_len()?, repositories.len()?),
+            format!("Reclaimed: {}{}", size_str(repositories.reclaimed()).trim(), staged),
+            format!("Scanned {} dirs in {:.1}s ({:.0} dirs/s)", self.directories, elapsed.as_secs_f64(), rate(self.directories, elapsed)),
+        ];
+        // Measured before painting, since the escape sequences take no room
+        let line = segments[..fitting_segments(&segments, SEPARATOR, (width as usize).saturating_sub(1))]
+            .join(SEPARATOR)
+            .replacen(&total_size_str, &theme.paint(theme.size_color(total_size), &total_size_str), 1);
+        terminal.clear(ClearType::CurrentLine)?;
+        terminal.write(format!("{}\r\n", line))?;
+        Ok(())
+    }
+}
+
+impl Default for Summary {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn rate(count: usize, elapsed: Duration) -> f64 {
+    let secs = elapsed.as_secs_f64();
+    if secs > 0.0 {
+        count as f64 / secs
+    } else {
+        0.0
+    }
+}
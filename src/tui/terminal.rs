@@ -0,0 +1,123 @@
+use std::sync::mpsc::Sender;
+use std::sync::{Mutex, Once};
+use std::thread::{self, ThreadId};
+
+use crossterm::{cursor, input, AlternateScreen, RawScreen};
+#[cfg(unix)]
+use signal_hook::consts::{SIGINT, SIGTERM, SIGTSTP, SIGWINCH};
+#[cfg(unix)]
+use signal_hook::iterator::Signals;
+
+use rpcl::Result;
+use crate::tui::event::Event;
+#[cfg(unix)]
+use crate::tui::event::Signal;
+
+static INSTALL_PANIC_HOOK: Once = Once::new();
+
+/// The alternate screen while the TUI is shown. Dropping it switches back to the main screen.
+static SCREEN: Mutex<Option<AlternateScreen>> = Mutex::new(None);
+
+/// The thread drawing the TUI, and where panics of other threads are reported while it runs.
+static TUI: Mutex<Option<(ThreadId, Sender<Event>)>> = Mutex::new(None);
+
+/// Puts the terminal into the state the TUI needs: the alternate screen in raw mode,
+/// with a hidden cursor and mouse reporting. Restores it when dropped or when the
+/// calling thread panics. Panics of other threads are sent to `events` instead,
+/// as the TUI keeps drawing until it handles them.
+pub struct TerminalGuard;
+
+impl TerminalGuard {
+    pub fn new(events: Sender<Event>) -> Result<Self> {
+        INSTALL_PANIC_HOOK.call_once(|| {
+            let default_hook = std::panic::take_hook();
+            std::panic::set_hook(Box::new(move |info| {
+                let tui = TUI.try_lock().ok().and_then(|tui| tui.clone());
+                match tui {
+                    Some((tui_thread, events)) if tui_thread != thread::current().id() => {
+                        let _ = events.send(Event::Panicked(info.to_string()));
+                    },
+                    Some(_) => {
+                        // Restore first so that the message ends up on the main screen
+                        restore();
+                        default_hook(info);
+                    },
+                    None => default_hook(info),
+                }
+            }));
+        });
+        *TUI.lock()? = Some((thread::current().id(), events));
+        enter()?;
+        Ok(TerminalGuard)
+    }
+
+    /// Restores the terminal and stops the process like Ctrl-Z does outside raw mode.
+    /// Takes the terminal over again once the process is resumed.
+    #[cfg(unix)]
+    pub fn suspend(&self) -> Result<()> {
+        restore();
+        signal_hook::low_level::emulate_default_handler(SIGTSTP)?;
+        enter()
+    }
+
+    /// Job control is Unix only, so there is nothing to suspend to.
+    #[cfg(not(unix))]
+    pub fn suspend(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore();
+        if let Ok(mut tui) = TUI.lock() {
+            *tui = None;
+        }
+    }
+}
+
+fn enter() -> Result<()> {
+    let mut raw = RawScreen::into_raw_mode()?;
+    // Raw mode is turned off by `restore`
+    raw.keep_raw_mode_on_drop();
+    *SCREEN.lock()? = Some(AlternateScreen::to_alternate(false)?);
+    cursor().hide()?;
+    input().enable_mouse_mode()?;
+    Ok(())
+}
+
+/// Undoes `enter`. Errors are ignored since this runs while bailing out anyway.
+fn restore() {
+    let _ = input().disable_mouse_mode();
+    let _ = cursor().show();
+    // A panic while the screen is being switched leaves it to the switch itself
+    if let Ok(mut screen) = SCREEN.try_lock() {
+        screen.take();
+    }
+    let _ = RawScreen::disable_raw_mode();
+}
+
+/// Forwards SIGINT, SIGTERM, SIGTSTP and SIGWINCH to the TUI, which handles them between draws.
+#[cfg(unix)]
+pub fn forward_signals(tx: Sender<Event>) -> Result<()> {
+    let mut signals = Signals::new([SIGINT, SIGTERM, SIGTSTP, SIGWINCH])?;
+    thread::spawn(move || {
+        for signal in signals.forever() {
+            let event = match signal {
+                SIGWINCH => Event::Resize,
+                SIGTSTP => Event::Signal(Signal::Suspend),
+                _ => Event::Signal(Signal::Quit),
+            };
+            if tx.send(event).is_err() {
+                break;
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Raw mode delivers Ctrl-C as a key press, and there are no other signals to forward.
+#[cfg(not(unix))]
+pub fn forward_signals(_tx: Sender<Event>) -> Result<()> {
+    Ok(())
+}
@@ -0,0 +1,147 @@
+//! Fitting text into terminal columns. Works on grapheme clusters and measures their display
+//! width, so that neither combining characters nor wide CJK and emoji characters get split or miscounted.
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthChar;
+
+const ELLIPSIS: &str = "…";
+
+/// The number of columns `s` takes up.
+pub fn width(s: &str) -> usize {
+    s.graphemes(true).map(grapheme_width).sum()
+}
+
+/// Terminals draw a cluster like 👩‍💻 as a single glyph, as wide as its widest character.
+fn grapheme_width(grapheme: &str) -> usize {
+    grapheme.chars().map(|c| c.width().unwrap_or(0)).max().unwrap_or(0)
+}
+
+/// The longest prefix of `s` that fits into `width` columns.
+pub fn truncate(s: &str, width: usize) -> &str {
+    let mut used = 0;
+    for (i, grapheme) in s.grapheme_indices(true) {
+        used += grapheme_width(grapheme);
+        if used > width {
+            return &s[..i];
+        }
+    }
+    s
+}
+
+/// `s` followed by enough spaces to take up `width` columns.
+pub fn pad(s: &str, width: usize) -> String {
+    format!("{}{}", s, " ".repeat(width.saturating_sub(self::width(s))))
+}
+
+/// The longest suffix of `s` that fits into `width` columns.
+fn truncate_start(s: &str, width: usize) -> &str {
+    let mut used = 0;
+    for (i, grapheme) in s.grapheme_indices(true).rev() {
+        used += grapheme_width(grapheme);
+        if used > width {
+            return &s[i + grapheme.len()..];
+        }
+    }
+    s
+}
+
+/// Fits `s` into `width` columns by replacing its middle with an ellipsis, e.g. `~/src/…/project`.
+/// The last path component is kept whole when there's room for it, since it tells paths apart best.
+pub fn truncate_middle(s: &str, width: usize) -> String {
+    if self::width(s) <= width {
+        return s.to_owned();
+    }
+    let budget = match width.checked_sub(self::width(ELLIPSIS)) {
+        Some(budget) => budget,
+        None => return String::new(),
+    };
+    let last_component = s.rfind('/').map(|i| self::width(&s[i..])).unwrap_or(0);
+    let tail_budget = std::cmp::min(std::cmp::max(budget - budget / 2, last_component), budget);
+    let tail = truncate_start(s, tail_budget);
+    let head = truncate(s, budget - self::width(tail));
+    format!("{}{}{}", head, ELLIPSIS, tail)
+}
+
+/// Fits `s` into `width` columns by showing it from the `offset`th grapheme on, like a marquee.
+/// The offset stops growing once the end of `s` is in view.
+pub fn scroll(s: &str, width: usize, offset: usize) -> &str {
+    if self::width(s) <= width {
+        return s;
+    }
+    let tail = truncate_start(s, width);
+    let start = s.grapheme_indices(true).nth(offset).map(|(i, _)| i).unwrap_or(s.len());
+    if start >= s.len() - tail.len() {
+        return tail;
+    }
+    truncate(&s[start..], width)
+}
+
+/// How many offsets `scroll` goes through before the end of `s` is in view.
+pub fn scroll_len(s: &str, width: usize) -> usize {
+    if self::width(s) <= width {
+        return 0;
+    }
+    let tail = truncate_start(s, width);
+    s[..s.len() - tail.len()].graphemes(true).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_width() {
+        assert_eq!(width("abc"), 3);
+        assert_eq!(width("日本語"), 6);
+        assert_eq!(width("🦀"), 2);
+        assert_eq!(width("👩‍💻"), 2);
+        assert_eq!(width("cafe\u{301}"), 4);
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("abcde", 3), "abc");
+        assert_eq!(truncate("abc", 3), "abc");
+        assert_eq!(truncate("日本語", 5), "日本");
+        assert_eq!(truncate("日本語", 1), "");
+        // The combining accent stays with its "e"
+        assert_eq!(truncate("cafe\u{301}s", 4), "cafe\u{301}");
+        assert_eq!(truncate("👩‍💻x", 2), "👩‍💻");
+    }
+
+    #[test]
+    fn test_pad() {
+        assert_eq!(pad("ab", 4), "ab  ");
+        assert_eq!(pad("日本", 5), "日本 ");
+        assert_eq!(pad("abcde", 3), "abcde");
+    }
+
+    #[test]
+    fn test_truncate_middle() {
+        assert_eq!(truncate_middle("~/src/project", 20), "~/src/project");
+        assert_eq!(truncate_middle("~/src/github.com/foo/project", 15), "~/src/…/project");
+        assert_eq!(truncate_middle("abcdefgh", 5), "ab…gh");
+        assert_eq!(truncate_middle("~/ソース/プロジェクト", 14), "…/プロジェクト");
+        assert_eq!(truncate_middle("~/写真/🦀🦀🦀/テスト", 9), "~…/テスト");
+        assert_eq!(truncate_middle("🦀🦀🦀🦀", 6), "🦀…🦀");
+        assert_eq!(truncate_middle("abc", 0), "");
+    }
+
+    #[test]
+    fn test_scroll() {
+        assert_eq!(scroll("", 3, 0), "");
+        assert_eq!(scroll("", 3, 2), "");
+        assert_eq!(scroll("abcde", 3, 100), "cde");
+        assert_eq!(scroll("abc", 3, 1), "abc");
+        assert_eq!(scroll("abcde", 3, 0), "abc");
+        assert_eq!(scroll("abcde", 3, 1), "bcd");
+        assert_eq!(scroll("abcde", 3, 2), "cde");
+        assert_eq!(scroll("abcde", 3, 3), "cde");
+        assert_eq!(scroll_len("abcde", 3), 2);
+        assert_eq!(scroll_len("abc", 3), 0);
+        assert_eq!(scroll_len("日本語x", 4), 2);
+        assert_eq!(scroll("日本語のパス", 5, 0), "日本");
+        assert_eq!(scroll("日本語のパス", 5, 1), "本語");
+        assert_eq!(scroll("日本語のパス", 5, 100), "パス");
+    }
+}
@@ -0,0 +1,189 @@
+use std::ffi::OsString;
+
+use crossterm::{Attribute, Color, Command, SetAttr, SetFg};
+
+use rpcl::ArtifactKind;
+
+const GIB: u64 = 1 << 30;
+const MIB: u64 = 1 << 20;
+
+pub const THEME_NAMES: &[&str] = &["dark", "light", "monochrome"];
+
+/// Colors used throughout the TUI. `None` means the terminal's default color.
+pub struct Theme {
+    /// Sizes over 1 GiB
+    pub huge: Option<Color>,
+    /// Sizes over 100 MiB
+    pub large: Option<Color>,
+    pub bar: Option<Color>,
+    pub rust: Option<Color>,
+    pub node: Option<Color>,
+    pub python: Option<Color>,
+    pub build: Option<Color>,
+    /// Whether secondary text such as parent directories is shown faint
+    pub faint: bool,
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Self {
+            huge: Some(Color::Red),
+            large: Some(Color::Yellow),
+            bar: Some(Color::Cyan),
+            rust: Some(Color::Magenta),
+            node: Some(Color::Green),
+            python: Some(Color::Blue),
+            build: Some(Color::Cyan),
+            faint: true,
+        }
+    }
+
+    pub fn light() -> Self {
+        Self {
+            huge: Some(Color::DarkRed),
+            large: Some(Color::DarkYellow),
+            bar: Some(Color::DarkCyan),
+            rust: Some(Color::DarkMagenta),
+            node: Some(Color::DarkGreen),
+            python: Some(Color::DarkBlue),
+            build: Some(Color::DarkCyan),
+            faint: true,
+        }
+    }
+
+    pub fn monochrome() -> Self {
+        Self {
+            huge: None,
+            large: None,
+            bar: None,
+            rust: None,
+            node: None,
+            python: None,
+            build: None,
+            faint: false,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            "monochrome" => Some(Self::monochrome()),
+            _ => None,
+        }
+    }
+
+    /// Picks the theme named on the command line, falling back to monochrome
+    /// when `NO_COLOR` is set and to the dark theme otherwise.
+    pub fn detect(name: Option<&str>) -> Self {
+        Self::detect_with(name, std::env::var_os("NO_COLOR"))
+    }
+
+    fn detect_with(name: Option<&str>, no_color: Option<OsString>) -> Self {
+        if let Some(theme) = name.and_then(Self::from_name) {
+            return theme;
+        }
+        match no_color {
+            Some(ref v) if !v.is_empty() => Self::monochrome(),
+            _ => Self::dark(),
+        }
+    }
+
+    pub fn size_color(&self, size: u64) -> Option<Color> {
+        if size > GIB {
+            self.huge
+        } else if size > 100 * MIB {
+            self.large
+        } else {
+            None
+        }
+    }
+
+    pub fn kind_color(&self, kind: ArtifactKind) -> Option<Color> {
+        match kind {
+            ArtifactKind::Rust => self.rust,
+            ArtifactKind::Node => self.node,
+            ArtifactKind::Python => self.python,
+            ArtifactKind::Build => self.build,
+            ArtifactKind::Other => None,
+        }
+    }
+
+    /// Wraps `text` in escape sequences setting and resetting the foreground color.
+    /// Only the foreground is reset so that the reverse video of a selected row survives,
+    /// which is why this doesn't use `crossterm::style`, whose reset clears every attribute.
+    pub fn paint(&self, color: Option<Color>, text: &str) -> String {
+        match color.filter(|c| *c != Color::Reset) {
+            Some(color) => format!("{}{}{}", SetFg(color).ansi_code(), text, SetFg(Color::Reset).ansi_code()),
+            None => text.to_string(),
+        }
+    }
+
+    /// Wraps `text` in escape sequences for faint text, unless the theme goes without them.
+    pub fn dim(&self, text: &str) -> String {
+        if self.faint {
+            format!("{}{}{}", SetAttr(Attribute::Dim).ansi_code(), text, SetAttr(Attribute::NormalIntensity).ansi_code())
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+const BAR_EIGHTHS: &[char] = &['▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+
+/// Renders `size` relative to `max` as a bar of `width` cells, padded with spaces.
+pub fn size_bar(size: u64, max: u64, width: usize) -> String {
+    let eighths = if max == 0 {
+        0
+    } else {
+        (size as u128 * width as u128 * 8 / max as u128) as usize
+    };
+    let eighths = std::cmp::min(eighths, width * 8);
+    let mut bar = "█".repeat(eighths / 8);
+    if eighths % 8 != 0 {
+        bar.push(BAR_EIGHTHS[eighths % 8 - 1]);
+    }
+    let len = bar.chars().count();
+    bar.push_str(&" ".repeat(width - len));
+    bar
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_size_bar() {
+        assert_eq!(size_bar(0, 0, 4), "    ");
+        assert_eq!(size_bar(10, 10, 4), "████");
+        assert_eq!(size_bar(5, 10, 4), "██  ");
+        assert_eq!(size_bar(1, 16, 2), "▏ ");
+        assert_eq!(size_bar(20, 10, 2), "██");
+    }
+
+    #[test]
+    fn test_paint() {
+        assert_eq!(Theme::dark().paint(Some(Color::Red), "x"), "\x1B[38;5;9mx\x1B[39m");
+        assert_eq!(Theme::dark().paint(Some(Color::Rgb { r: 1, g: 2, b: 3 }), "x"), "\x1B[38;2;1;2;3mx\x1B[39m");
+        assert_eq!(Theme::dark().paint(None, "x"), "x");
+        assert_eq!(Theme::dark().paint(Some(Color::Reset), "x"), "x");
+    }
+
+    #[test]
+    fn test_dim() {
+        assert_eq!(Theme::dark().dim("x"), "\x1B[2mx\x1B[22m");
+        assert_eq!(Theme::monochrome().dim("x"), "x");
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(Theme::detect_with(None, None).huge, Some(Color::Red));
+        assert_eq!(Theme::detect_with(None, Some(OsString::from("1"))).huge, None);
+        // An empty NO_COLOR doesn't count as set
+        assert_eq!(Theme::detect_with(None, Some(OsString::new())).huge, Some(Color::Red));
+        // The theme given with --theme wins over NO_COLOR
+        assert_eq!(Theme::detect_with(Some("light"), Some(OsString::from("1"))).huge, Some(Color::DarkRed));
+        assert_eq!(Theme::detect_with(Some("monochrome"), None).huge, None);
+        assert_eq!(Theme::detect_with(Some("unknown"), None).huge, Some(Color::Red));
+    }
+}
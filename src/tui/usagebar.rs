@@ -1,21 +1,88 @@
-use crossterm::ClearType;
+use crossterm::{ClearType, KeyEvent};
 
 use crate::tui::app::AppState;
+use crate::tui::keymap::{key_name, Action, Keymap};
+use crate::tui::layout::fitting_segments;
 
 pub struct UsageBar;
 
+const SEPARATOR: &str = " | ";
+
+/// Hints shown for each state, as the actions to show the first key of and a label.
+/// Clicking a hint with a single action triggers the action.
+fn hints(state: &AppState) -> &'static [(&'static [Action], &'static str)] {
+    match state {
+        AppState::PathList => &[
+            (&[Action::Down, Action::Up], "Move"),
+            (&[Action::Open], "Details"),
+            (&[Action::Quit], "Quit"),
+            (&[Action::Help], "Help"),
+        ],
+        AppState::Details { .. } => &[
+            (&[Action::Down, Action::Up], "Move"),
+            (&[Action::Back], "Back to list"),
+            (&[Action::Toggle], "Select"),
+            (&[Action::Delete], "Delete"),
+            (&[Action::Quit], "Quit"),
+            (&[Action::Help], "Help"),
+        ],
+    }
+}
+
+/// The hints for `state` with the keys of `keymap`, leaving out actions without keys.
+fn hint_strs(state: &AppState, keymap: &Keymap) -> Vec<(String, Option<KeyEvent>)> {
+    hints(state).iter()
+        .filter(|(actions, _)| actions.iter().all(|action| !keymap.keys(*action).is_empty()))
+        .map(|(actions, label)| {
+            let keys = actions.iter().map(|action| key_name(&keymap.keys(*action)[0])).collect::<Vec<_>>();
+            let key = if actions.len() == 1 { keymap.keys(actions[0]).first().cloned() } else { None };
+            (format!("{}: {}", keys.join(","), label), key)
+        })
+        .collect()
+}
+
 impl UsageBar {
-    pub fn draw(&self, state: &AppState) -> crossterm::Result<()> {
+    pub fn draw(&self, state: &AppState, keymap: &Keymap) -> crossterm::Result<()> {
         let terminal = crossterm::terminal();
+        let (width, _height) = terminal.size()?;
         terminal.clear(ClearType::CurrentLine)?;
-        match state {
-            &AppState::PathList => {
-                terminal.write("j,k: Move | Enter: Details | q: Quit | h: Help\r\n")?;
-            },
-            &AppState::Details(_) => {
-                terminal.write("j,k: Move | Enter: Back to list | d: Delete | q: Quit | h: Help\r\n")?;
-            },
-        }
+        // Hints that don't fit are left out; the help window lists them all anyway
+        let hints = hint_strs(state, keymap).into_iter().map(|(hint, _)| hint).collect::<Vec<_>>();
+        let line = hints[..fitting_segments(&hints, SEPARATOR, (width as usize).saturating_sub(1))].join(SEPARATOR);
+        terminal.write(format!("{}\r\n", line))?;
         Ok(())
     }
+
+    /// Returns the key bound to the hint at the given (1-based) mouse column.
+    pub fn key_at(&self, state: &AppState, keymap: &Keymap, mouse_x: u16) -> Option<KeyEvent> {
+        let x = (mouse_x as usize).checked_sub(1)?;
+        let mut start = 0;
+        for (hint, key) in hint_strs(state, keymap) {
+            let end = start + hint.chars().count();
+            if x >= start && x < end {
+                return key;
+            }
+            start = end + SEPARATOR.len();
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_at() {
+        let bar = UsageBar;
+        let keymap = Keymap::new();
+        assert_eq!(bar.key_at(&AppState::PathList, &keymap, 1), None);
+        assert_eq!(bar.key_at(&AppState::PathList, &keymap, 13), Some(KeyEvent::Enter));
+        assert_eq!(bar.key_at(&AppState::PathList, &keymap, 27), None);
+        assert_eq!(bar.key_at(&AppState::PathList, &keymap, 30), Some(KeyEvent::Char('q')));
+        assert_eq!(bar.key_at(&AppState::PathList, &keymap, 100), None);
+        let keymap = keymap.with_keys(Action::Open, vec![KeyEvent::Char('l'), KeyEvent::Enter]);
+        assert_eq!(bar.key_at(&AppState::PathList, &keymap, 13), Some(KeyEvent::Char('l')));
+        assert_eq!(bar.key_at(&AppState::PathList, &keymap, 26), Some(KeyEvent::Char('q')));
+    }
 }
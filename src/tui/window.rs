@@ -1,52 +1,88 @@
-use strfmt::strfmt;
-use std::collections::HashMap;
+use std::cmp::min;
+
+use rpcl::Result;
+use crate::tui::text;
+
+/// How much blank space is kept around the message when there's room for it.
+const PADDING: u16 = 2;
 
 pub struct Window {
     pub message: Vec<String>,
+    /// The first line shown when the message is too long for the terminal
+    pub offset: usize,
 }
 
 impl Window {
-    pub fn draw(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let message_width = self.message.iter().map(|m| m.len() as u16).max().expect("Invalid message");
-        let message_height = self.message.len() as u16;
+    pub fn new(message: Vec<String>) -> Self {
+        Self { message, offset: 0 }
+    }
+
+    /// The largest useful offset, at which the end of the message is in view.
+    pub fn max_offset(&self) -> Result<usize> {
+        let (_width, height) = crossterm::terminal().size()?;
+        Ok(self.message.len().saturating_sub(visible_lines(self.message.len(), height)))
+    }
+
+    /// Moves the offset by `lines`, within the message.
+    pub fn scroll_by(&mut self, lines: isize) -> Result<()> {
+        let offset = if lines < 0 { self.offset.saturating_sub(lines.unsigned_abs()) } else { self.offset.saturating_add(lines as usize) };
+        self.offset = min(offset, self.max_offset()?);
+        Ok(())
+    }
+
+    /// Draws the message centered. On a terminal too small for it, the padding shrinks first,
+    /// then lines are cut off on the right, and the lines from `offset` on are shown with their position below.
+    pub fn draw(&self) -> Result<()> {
         let terminal = crossterm::terminal();
         let cursor = crossterm::cursor();
         let (terminal_width, terminal_height) = terminal.size()?;
-        if terminal_width < message_width || terminal_height < message_height {
-            // return an Error
-            return Ok(());
-        }
-        let x = terminal_width / 2 - message_width / 2;
-        let y = terminal_height / 2 - message_height / 2;
-        let padding = 2;
-        if x < padding || y < padding {
-            // return an Error
-            return Ok(());
+        let visible = visible_lines(self.message.len(), terminal_height);
+        let offset = min(self.offset, self.message.len() - visible);
+        let mut lines = self.message[offset..offset + visible].to_vec();
+        if visible < self.message.len() {
+            lines.push(format!("({}-{} of {} lines)", offset + 1, offset + visible, self.message.len()));
         }
-        for y in (y - padding)..y {
-            cursor.goto(x - padding, y)?;
-            for _ in 0..(message_width + padding * 2) {
-                terminal.write(" ")?;
-            }
+        let message_width = lines.iter().map(|m| text::width(m) as u16).max().unwrap_or(0);
+        // The last column is left alone since writing there may scroll the screen
+        let message_width = min(message_width, terminal_width.saturating_sub(1));
+        let message_height = lines.len() as u16;
+        let padding_x = min(PADDING, (terminal_width.saturating_sub(1) - message_width) / 2);
+        let padding_y = min(PADDING, terminal_height.saturating_sub(message_height) / 2);
+        let x = (terminal_width.saturating_sub(1) - message_width) / 2;
+        let y = terminal_height.saturating_sub(message_height) / 2;
+        let blank = " ".repeat((message_width + padding_x * 2) as usize);
+        for y in (y - padding_y)..y {
+            cursor.goto(x - padding_x, y)?;
+            terminal.write(&blank)?;
         }
         for (i, y) in (y..(y + message_height)).enumerate() {
-            cursor.goto(x - padding, y)?;
-            let mut map = HashMap::new();
-            map.insert("message".to_string(), &self.message[i]);
-            for _ in 0..padding {
-                terminal.write(" ")?;
-            }
-            terminal.write(strfmt(&format!("{{message:<{}}}", message_width), &map)?)?;
-            for _ in 0..padding {
-                terminal.write(" ")?;
-            }
+            cursor.goto(x - padding_x, y)?;
+            let line = text::pad(text::truncate(&lines[i], message_width as usize), message_width as usize);
+            terminal.write(format!("{:pad$}{}{:pad$}", "", line, "", pad = padding_x as usize))?;
         }
-        for y in (y + message_height)..(y + message_height + padding) {
-            cursor.goto(x - padding, y)?;
-            for _ in 0..(message_width + padding * 2) {
-                terminal.write(" ")?;
-            }
+        for y in (y + message_height)..(y + message_height + padding_y) {
+            cursor.goto(x - padding_x, y)?;
+            terminal.write(&blank)?;
         }
         Ok(())
     }
 }
+
+/// How many lines of a message fit into `height` rows, leaving one for the position when not all of them do.
+fn visible_lines(len: usize, height: u16) -> usize {
+    let height = height as usize;
+    if len <= height { len } else { height.saturating_sub(1) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_visible_lines() {
+        assert_eq!(visible_lines(5, 10), 5);
+        assert_eq!(visible_lines(10, 10), 10);
+        assert_eq!(visible_lines(20, 10), 9);
+        assert_eq!(visible_lines(20, 0), 0);
+    }
+}